@@ -0,0 +1,159 @@
+//! '#[derive(PlyElement)]' implements 'ply::PropertyAccess' for a struct with named fields, so
+//! that an element can be loaded with 'ply::ElementReader::read_into'.
+//!
+//! Every field is read from the property of the same name, '#[ply(name = "...")]' picks another
+//! one. Fields are numbers for scalar properties and 'Vec's of numbers for list properties. Before
+//! the first record is read, the element is checked to have every property and for every type to
+//! be convertible to the field without loss, e.g. 'uchar' to 'u32' or 'int' to 'f64'. Fields of
+//! type 'Option' may be missing from the element and are 'None' then.
+//!
+//! ```ignore
+//! #[derive(PlyElement)]
+//! struct Vertex {
+//!     x: f32,
+//!     y: f32,
+//!     z: f32,
+//!     #[ply(name = "red")]
+//!     r: Option<u8>,
+//! }
+//!
+//! #[derive(PlyElement)]
+//! struct Face {
+//!     vertex_indices: Vec<u32>,
+//! }
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Data, DataStruct, DeriveInput, Error, Fields, GenericArgument, LitStr, PathArguments,
+          Type};
+
+/// The 'Option<T>' argument 'T' if 'ty' is an 'Option'.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let path = match *ty {
+        Type::Path(ref path) if path.qself.is_none() => &path.path,
+        _ => return None,
+    };
+    let last = path.segments.last()?;
+    if last.ident != "Option" {
+        return None;
+    }
+    match last.arguments {
+        PathArguments::AngleBracketed(ref arguments) if arguments.args.len() == 1 => {
+            match arguments.args[0] {
+                GenericArgument::Type(ref inner) => Some(inner),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The property a field is read from: its name or the one given with '#[ply(name = "...")]'.
+fn property_name(field: &syn::Field) -> syn::Result<String> {
+    let ident = field.ident.as_ref().expect("only named fields are derived");
+    let mut name = ident.to_string().trim_start_matches("r#").to_string();
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("ply")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("unknown ply attribute, expected 'name'"))
+            }
+        })?;
+    }
+    Ok(name)
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match input.data {
+        Data::Struct(DataStruct { fields: Fields::Named(ref fields), .. }) => &fields.named,
+        _ => {
+            return Err(Error::new_spanned(input,
+                                          "PlyElement can only be derived for structs with \
+                                           named fields"))
+        }
+    };
+
+    let mut names = Vec::<String>::new();
+    let mut idents = Vec::new();
+    let mut sets = Vec::new();
+    let mut checks = Vec::new();
+    for field in fields {
+        let ident = &field.ident;
+        let name = property_name(field)?;
+        if names.contains(&name) {
+            return Err(Error::new_spanned(field,
+                                          format!("property '{}' is read into two fields", name)));
+        }
+        let (ty, set, missing) = match option_inner(&field.ty) {
+            Some(inner) => {
+                (inner,
+                 quote! { self.#ident = <#inner as ::ply::FromProperty>::from_property(value) },
+                 quote! { () })
+            }
+            None => {
+                let ty = &field.ty;
+                (ty,
+                 quote! {
+                     if let ::std::option::Option::Some(value) =
+                            <#ty as ::ply::FromProperty>::from_property(value) {
+                         self.#ident = value;
+                     }
+                 },
+                 quote! { return ::std::result::Result::Err(#name.to_string()) })
+            }
+        };
+        checks.push(quote! {
+            match element.properties.iter().find(|property| property.name == #name) {
+                ::std::option::Option::Some(property) => {
+                    if !<#ty as ::ply::FromProperty>::accepts(&property.kind) {
+                        return ::std::result::Result::Err(#name.to_string());
+                    }
+                }
+                ::std::option::Option::None => #missing,
+            }
+        });
+        sets.push(set);
+        idents.push(ident);
+        names.push(name);
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::ply::PropertyAccess for #ident #ty_generics #where_clause {
+            fn new() -> Self {
+                #ident { #(#idents: ::std::default::Default::default(),)* }
+            }
+
+            fn set_property(&mut self, name: &str, value: ::ply::PropertyValue) {
+                match name {
+                    #(#names => { #sets })*
+                    _ => (),
+                }
+            }
+
+            fn check(element: &::ply::Element)
+                     -> ::std::result::Result<(), ::std::string::String> {
+                #(#checks)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+/// Implements 'ply::PropertyAccess', see the crate documentation.
+#[proc_macro_derive(PlyElement, attributes(ply))]
+pub fn derive_ply_element(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
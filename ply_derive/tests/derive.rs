@@ -0,0 +1,54 @@
+extern crate ply;
+#[macro_use]
+extern crate ply_derive;
+
+use ply::{PlyError, Reader};
+
+#[derive(Debug, PartialEq, PlyElement)]
+struct Vertex {
+    x: f32,
+    #[ply(name = "y")]
+    height: f64,
+    red: Option<u8>,
+    confidence: Option<f32>,
+}
+
+#[derive(Debug, PartialEq, PlyElement)]
+struct Face {
+    vertex_indices: Vec<u32>,
+}
+
+#[test]
+fn derive_test() {
+    let input = b"ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty int y\n\
+                  property uchar red\nelement face 1\nproperty list uchar ushort vertex_indices\n\
+                  end_header\n0.5 1 255\n1.5 -2 0\n3 0 1 1\n";
+    let mut reader = Reader::new(&input[..]).unwrap();
+    let vertices = reader.next_element().unwrap().unwrap().read_into::<Vertex>().unwrap();
+    assert_eq!(vec![Vertex { x: 0.5, height: 1.0, red: Some(255), confidence: None },
+                    Vertex { x: 1.5, height: -2.0, red: Some(0), confidence: None }],
+               vertices);
+    let faces = reader.next_element().unwrap().unwrap().read_into::<Face>().unwrap();
+    assert_eq!(vec![Face { vertex_indices: vec![0, 1, 1] }], faces);
+}
+
+#[test]
+fn derive_incompatible_test() {
+    let read_vertices = |input: &[u8]| {
+        let mut reader = Reader::new(input).unwrap();
+        let mut element = reader.next_element().unwrap().unwrap();
+        element.read_into::<Vertex>()
+    };
+    // 'y' is missing.
+    let result = read_vertices(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+                                 end_header\n0\n");
+    assert!(matches!(result,
+                     Err(PlyError::IncompatibleProperty { element: 0, ref property })
+                         if property == "y"));
+    // 'red' is optional, but cannot be read from a float if present.
+    let result = read_vertices(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+                                 property float y\nproperty float red\nend_header\n0 0 0\n");
+    assert!(matches!(result,
+                     Err(PlyError::IncompatibleProperty { element: 0, ref property })
+                         if property == "red"));
+}
@@ -0,0 +1,145 @@
+use body::{PropertyValue, Record, Value};
+use header::{Element, PropertyKind, ValueKind};
+
+/// A type that the records of an element can be read into directly, see
+/// 'ElementReader::read_into'. '#[derive(PlyElement)]' from the 'ply_derive' crate implements it
+/// for structs with one field per property.
+///
+/// ```
+/// use ply::{PropertyAccess, PropertyValue, Value};
+///
+/// #[derive(Default)]
+/// struct Vertex {
+///     x: f32,
+///     y: f32,
+/// }
+///
+/// impl PropertyAccess for Vertex {
+///     fn new() -> Self {
+///         Vertex::default()
+///     }
+///
+///     fn set_property(&mut self, name: &str, value: PropertyValue) {
+///         match (name, value) {
+///             ("x", PropertyValue::Scalar(Value::Float32(x))) => self.x = x,
+///             ("y", PropertyValue::Scalar(Value::Float32(y))) => self.y = y,
+///             _ => (),
+///         }
+///     }
+/// }
+/// ```
+pub trait PropertyAccess {
+    /// Creates the value a record is read into, before any of its properties are set.
+    fn new() -> Self;
+
+    /// Sets the property 'name' of the record. Called once per property in declaration order.
+    /// Properties the type has no use for should be ignored.
+    fn set_property(&mut self, name: &str, value: PropertyValue);
+
+    /// Checks that records of 'element' can be read into this type before the first one is read.
+    /// Returns the name of the offending property otherwise. The default accepts every element.
+    fn check(element: &Element) -> Result<(), String> {
+        let _ = element;
+        Ok(())
+    }
+}
+
+/// Moves the values of 'record', a record of 'element', into a new 'T'.
+pub fn from_record<T: PropertyAccess>(element: &Element, record: Record) -> T {
+    let mut result = T::new();
+    for (property, value) in element.properties.iter().zip(record) {
+        result.set_property(&property.name, value);
+    }
+    result
+}
+
+/// Scalar types a value can be converted to without loss. Used by '#[derive(PlyElement)]'.
+#[doc(hidden)]
+pub trait FromValue: Sized {
+    fn accepts(kind: ValueKind) -> bool;
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+macro_rules! from_value {
+    ($t:ty: $($kind:ident),*) => {
+        impl FromValue for $t {
+            fn accepts(kind: ValueKind) -> bool {
+                matches!(kind, $(ValueKind::$kind)|*)
+            }
+
+            fn from_value(value: Value) -> Option<Self> {
+                match value {
+                    $(Value::$kind(v) => Some(<$t>::from(v)),)*
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+from_value!(i8: Int8);
+from_value!(u8: UInt8);
+from_value!(i16: Int8, UInt8, Int16);
+from_value!(u16: UInt8, UInt16);
+from_value!(i32: Int8, UInt8, Int16, UInt16, Int32);
+from_value!(u32: UInt8, UInt16, UInt32);
+from_value!(i64: Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64);
+from_value!(u64: UInt8, UInt16, UInt32, UInt64);
+from_value!(f32: Int8, UInt8, Int16, UInt16, Float32);
+from_value!(f64: Int8, UInt8, Int16, UInt16, Int32, UInt32, Float32, Float64);
+
+/// Field types of a '#[derive(PlyElement)]' struct: scalars for scalar properties and 'Vec's of
+/// scalars for list properties.
+#[doc(hidden)]
+pub trait FromProperty: Sized {
+    fn accepts(kind: &PropertyKind) -> bool;
+    fn from_property(value: PropertyValue) -> Option<Self>;
+}
+
+impl<T: FromValue> FromProperty for T {
+    fn accepts(kind: &PropertyKind) -> bool {
+        match *kind {
+            PropertyKind::Scalar(kind) => T::accepts(kind),
+            PropertyKind::List(..) => false,
+        }
+    }
+
+    fn from_property(value: PropertyValue) -> Option<Self> {
+        match value {
+            PropertyValue::Scalar(value) => T::from_value(value),
+            PropertyValue::List(_) => None,
+        }
+    }
+}
+
+impl<T: FromValue> FromProperty for Vec<T> {
+    fn accepts(kind: &PropertyKind) -> bool {
+        match *kind {
+            PropertyKind::Scalar(_) => false,
+            PropertyKind::List(_, kind) => T::accepts(kind),
+        }
+    }
+
+    fn from_property(value: PropertyValue) -> Option<Self> {
+        match value {
+            PropertyValue::Scalar(_) => None,
+            PropertyValue::List(values) => values.into_iter().map(T::from_value).collect(),
+        }
+    }
+}
+
+#[test]
+fn from_property_test() {
+    assert!(<f64 as FromProperty>::accepts(&PropertyKind::Scalar(ValueKind::Int32)));
+    assert!(!<f32 as FromProperty>::accepts(&PropertyKind::Scalar(ValueKind::Int32)));
+    assert!(!<u32 as FromProperty>::accepts(&PropertyKind::Scalar(ValueKind::Int8)));
+    assert!(<Vec<u32> as FromProperty>::accepts(&PropertyKind::List(ValueKind::UInt8,
+                                                                    ValueKind::UInt16)));
+    assert!(!<u32 as FromProperty>::accepts(&PropertyKind::List(ValueKind::UInt8,
+                                                                ValueKind::UInt16)));
+    assert_eq!(Some(-3.0),
+               f64::from_property(PropertyValue::Scalar(Value::Int16(-3))));
+    assert_eq!(Some(vec![1u32, 2]),
+               Vec::<u32>::from_property(PropertyValue::List(vec![Value::UInt8(1),
+                                                                  Value::UInt16(2)])));
+}
@@ -0,0 +1,502 @@
+use error::PlyError;
+use header::{Element, FormatKind, Header, PropertyKind, ValueKind};
+use nom::{Err, IResult, is_space, multispace, be_u8, be_i8};
+use std::collections::HashMap;
+use std::str::from_utf8;
+use std::str::FromStr;
+
+/// A single scalar value as stored in the body of a PLY file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Value {
+    Int8(i8),
+    UInt8(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Float32(f32),
+    Float64(f64),
+}
+
+/// The value of one property of a record, mirroring 'PropertyKind'.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PropertyValue {
+    Scalar(Value),
+    List(Vec<Value>),
+}
+
+/// A single decoded element, values are in the order of 'Element::properties'.
+pub type Record = Vec<PropertyValue>;
+
+/// A fully decoded PLY file. 'payload' maps each element name to its records.
+#[derive(Debug, PartialEq)]
+pub struct Ply {
+    pub header: Header,
+    pub payload: HashMap<String, Vec<Record>>,
+}
+
+/// Whether 'token' spells out an infinite value rather than a number too large for its type.
+fn is_infinity(token: &str) -> bool {
+    let magnitude = token.trim_start_matches(['+', '-']);
+    magnitude.eq_ignore_ascii_case("inf") || magnitude.eq_ignore_ascii_case("infinity")
+}
+
+/// Parses 'token' as an 'f32', rejecting finite values that are out of range for an 'f32'. Only
+/// an explicit 'inf' or 'infinity' may be infinite.
+fn parse_f32(token: &str) -> Result<f32, ()> {
+    match f32::from_str(token) {
+        Ok(value) if !value.is_infinite() || is_infinity(token) => Ok(value),
+        _ => Err(()),
+    }
+}
+
+/// Like 'parse_f32', for an 'f64'.
+fn parse_f64(token: &str) -> Result<f64, ()> {
+    match f64::from_str(token) {
+        Ok(value) if !value.is_infinite() || is_infinity(token) => Ok(value),
+        _ => Err(()),
+    }
+}
+
+// A single whitespace separated token of an ASCII body.
+named!(ascii_token<&'a str>,
+    chain!(
+        opt!(multispace) ~
+        // Running out of input before a token is a truncated body, not an invalid one.
+        peek!(take!(1)) ~
+        token: map_res!(is_not!(b" \t\r\n"), from_utf8) ~
+        opt!(multispace),
+        || token
+    )
+);
+
+fn ascii_value(input: &[u8], value_kind: ValueKind) -> IResult<&[u8], Value> {
+    match value_kind {
+        ValueKind::Int8 => map!(input, map_res!(ascii_token, i8::from_str), Value::Int8),
+        ValueKind::UInt8 => map!(input, map_res!(ascii_token, u8::from_str), Value::UInt8),
+        ValueKind::Int16 => map!(input, map_res!(ascii_token, i16::from_str), Value::Int16),
+        ValueKind::UInt16 => map!(input, map_res!(ascii_token, u16::from_str), Value::UInt16),
+        ValueKind::Int32 => map!(input, map_res!(ascii_token, i32::from_str), Value::Int32),
+        ValueKind::UInt32 => map!(input, map_res!(ascii_token, u32::from_str), Value::UInt32),
+        ValueKind::Int64 => map!(input, map_res!(ascii_token, i64::from_str), Value::Int64),
+        ValueKind::UInt64 => map!(input, map_res!(ascii_token, u64::from_str), Value::UInt64),
+        ValueKind::Float32 => map!(input, map_res!(ascii_token, parse_f32), Value::Float32),
+        ValueKind::Float64 => map!(input, map_res!(ascii_token, parse_f64), Value::Float64),
+    }
+}
+
+pub fn binary_value(input: &[u8],
+                    value_kind: ValueKind,
+                    big_endian: bool)
+                    -> IResult<&[u8], Value> {
+    match value_kind {
+        ValueKind::Int8 => map!(input, be_i8, Value::Int8),
+        ValueKind::UInt8 => map!(input, be_u8, Value::UInt8),
+        ValueKind::Int16 => map!(input, i16!(big_endian), Value::Int16),
+        ValueKind::UInt16 => map!(input, u16!(big_endian), Value::UInt16),
+        ValueKind::Int32 => map!(input, i32!(big_endian), Value::Int32),
+        ValueKind::UInt32 => map!(input, u32!(big_endian), Value::UInt32),
+        ValueKind::Int64 => map!(input, i64!(big_endian), Value::Int64),
+        ValueKind::UInt64 => map!(input, u64!(big_endian), Value::UInt64),
+        ValueKind::Float32 => {
+            map!(input, u32!(big_endian), |bits| Value::Float32(f32::from_bits(bits)))
+        }
+        ValueKind::Float64 => {
+            map!(input, u64!(big_endian), |bits| Value::Float64(f64::from_bits(bits)))
+        }
+    }
+}
+
+fn value<'a>(input: &'a [u8],
+             format_kind: &FormatKind,
+             value_kind: ValueKind)
+             -> IResult<&'a [u8], Value> {
+    match *format_kind {
+        FormatKind::Ascii => ascii_value(input, value_kind),
+        FormatKind::LittleEndian => binary_value(input, value_kind, false),
+        FormatKind::BigEndian => binary_value(input, value_kind, true),
+    }
+}
+
+/// Interprets a decoded list count. Negative or non-integer counts are invalid.
+pub fn list_length(count: Value) -> Option<usize> {
+    match count {
+        Value::Int8(v) if v >= 0 => Some(v as usize),
+        Value::UInt8(v) => Some(v as usize),
+        Value::Int16(v) if v >= 0 => Some(v as usize),
+        Value::UInt16(v) => Some(v as usize),
+        Value::Int32(v) if v >= 0 => Some(v as usize),
+        Value::UInt32(v) => Some(v as usize),
+        Value::Int64(v) if v >= 0 => Some(v as usize),
+        Value::UInt64(v) => Some(v as usize),
+        _ => None,
+    }
+}
+
+fn list_value<'a>(input: &'a [u8],
+                  format_kind: &FormatKind,
+                  count_kind: ValueKind,
+                  item_kind: ValueKind)
+                  -> IResult<&'a [u8], Vec<Value>> {
+    let count = map_opt!(input, call!(value, format_kind, count_kind), list_length);
+    let (mut input, count) = match count {
+        IResult::Done(remaining, count) => (remaining, count),
+        IResult::Error(err) => return IResult::Error(err),
+        IResult::Incomplete(needed) => return IResult::Incomplete(needed),
+    };
+    // The count comes straight from the file, so do not trust it for preallocation.
+    let mut items = Vec::with_capacity(count.min(input.len()));
+    for _ in 0..count {
+        match value(input, format_kind, item_kind) {
+            IResult::Done(remaining, item) => {
+                input = remaining;
+                items.push(item);
+            }
+            IResult::Error(err) => return IResult::Error(err),
+            IResult::Incomplete(needed) => return IResult::Incomplete(needed),
+        }
+    }
+    IResult::Done(input, items)
+}
+
+fn property_value<'a>(input: &'a [u8],
+                      format_kind: &FormatKind,
+                      property_kind: &PropertyKind)
+                      -> IResult<&'a [u8], PropertyValue> {
+    match *property_kind {
+        PropertyKind::Scalar(value_kind) => {
+            map!(input, call!(value, format_kind, value_kind), PropertyValue::Scalar)
+        }
+        PropertyKind::List(count_kind, item_kind) => {
+            map!(input,
+                 call!(list_value, format_kind, count_kind, item_kind),
+                 PropertyValue::List)
+        }
+    }
+}
+
+fn record<'a>(mut input: &'a [u8],
+              format_kind: &FormatKind,
+              element: &Element)
+              -> IResult<&'a [u8], Record> {
+    let mut record = Vec::with_capacity(element.properties.len());
+    for property in &element.properties {
+        match property_value(input, format_kind, &property.kind) {
+            IResult::Done(remaining, value) => {
+                input = remaining;
+                record.push(value);
+            }
+            IResult::Error(err) => return IResult::Error(err),
+            IResult::Incomplete(needed) => return IResult::Incomplete(needed),
+        }
+    }
+    IResult::Done(input, record)
+}
+
+/// Steps over a value of 'property_kind' without decoding it. Binary values are skipped by size,
+/// ASCII values are only split off as tokens. List counts are decoded, they define the structure.
+fn skip_property<'a>(input: &'a [u8],
+                     format_kind: &FormatKind,
+                     property_kind: &PropertyKind)
+                     -> IResult<&'a [u8], ()> {
+    match *property_kind {
+        PropertyKind::Scalar(value_kind) => skip_values(input, format_kind, value_kind, 1),
+        PropertyKind::List(count_kind, item_kind) => {
+            match map_opt!(input, call!(value, format_kind, count_kind), list_length) {
+                IResult::Done(remaining, count) => {
+                    skip_values(remaining, format_kind, item_kind, count)
+                }
+                IResult::Error(err) => IResult::Error(err),
+                IResult::Incomplete(needed) => IResult::Incomplete(needed),
+            }
+        }
+    }
+}
+
+fn skip_values<'a>(mut input: &'a [u8],
+                   format_kind: &FormatKind,
+                   value_kind: ValueKind,
+                   count: usize)
+                   -> IResult<&'a [u8], ()> {
+    if *format_kind != FormatKind::Ascii {
+        let size = count.saturating_mul(value_kind.size());
+        return map!(input, take!(size), |_| ());
+    }
+    for _ in 0..count {
+        match ascii_token(input) {
+            IResult::Done(remaining, _) => input = remaining,
+            IResult::Error(err) => return IResult::Error(err),
+            IResult::Incomplete(needed) => return IResult::Incomplete(needed),
+        }
+    }
+    IResult::Done(input, ())
+}
+
+/// Like 'record', but only decodes the properties for which 'keep' is set and skips the others.
+fn projected_record<'a>(mut input: &'a [u8],
+                        format_kind: &FormatKind,
+                        element: &Element,
+                        keep: &[bool])
+                        -> IResult<&'a [u8], Record> {
+    let mut record = Vec::with_capacity(keep.iter().filter(|&&keep| keep).count());
+    for (property, &keep) in element.properties.iter().zip(keep) {
+        if keep {
+            match property_value(input, format_kind, &property.kind) {
+                IResult::Done(remaining, value) => {
+                    input = remaining;
+                    record.push(value);
+                }
+                IResult::Error(err) => return IResult::Error(err),
+                IResult::Incomplete(needed) => return IResult::Incomplete(needed),
+            }
+        } else {
+            match skip_property(input, format_kind, &property.kind) {
+                IResult::Done(remaining, ()) => input = remaining,
+                IResult::Error(err) => return IResult::Error(err),
+                IResult::Incomplete(needed) => return IResult::Incomplete(needed),
+            }
+        }
+    }
+    IResult::Done(input, record)
+}
+
+/// The position in the input at which a nom error occurred, if it carries one.
+fn error_position<'a>(err: &Err<&'a [u8]>) -> Option<&'a [u8]> {
+    match *err {
+        Err::Position(_, position) | Err::NodePosition(_, position, _) => Some(position),
+        Err::Code(_) | Err::Node(..) => None,
+    }
+}
+
+/// Decodes the next record of the element at 'index' from 'input', which starts at file
+/// position 'offset'. Returns 'None' if 'input' ends before the record is complete.
+pub fn decode_record<'a>(input: &'a [u8],
+                         offset: usize,
+                         header: &Header,
+                         index: usize)
+                         -> Result<Option<(&'a [u8], Record)>, PlyError> {
+    decoded(input,
+            offset,
+            index,
+            record(input, &header.format.kind, &header.elements[index]))
+}
+
+/// Like 'decode_record', but only decodes the properties for which 'keep' is set. The others are
+/// skipped over, checking only list counts and, for ASCII, the number of tokens.
+pub fn decode_projected<'a>(input: &'a [u8],
+                            offset: usize,
+                            header: &Header,
+                            index: usize,
+                            keep: &[bool])
+                            -> Result<Option<(&'a [u8], Record)>, PlyError> {
+    decoded(input,
+            offset,
+            index,
+            projected_record(input, &header.format.kind, &header.elements[index], keep))
+}
+
+/// Turns the result of decoding a record that starts at file position 'offset' into the result of
+/// 'decode_record'.
+fn decoded<'a, T>(input: &'a [u8],
+                  offset: usize,
+                  index: usize,
+                  result: IResult<&'a [u8], T>)
+                  -> Result<Option<(&'a [u8], T)>, PlyError> {
+    match result {
+        IResult::Done(remaining, record) => Ok(Some((remaining, record))),
+        IResult::Error(err) => {
+            let position = error_position(&err).unwrap_or(input);
+            Err(PlyError::InvalidNumber {
+                offset: offset + input.len() - position.len(),
+                element: index,
+            })
+        }
+        IResult::Incomplete(_) => Ok(None),
+    }
+}
+
+/// The size of the next binary record of 'element' in 'input', found without decoding its
+/// values. Returns 'None' if 'input' ends before the record does, or the position of an invalid
+/// list count.
+pub fn binary_record_len(input: &[u8],
+                         element: &Element,
+                         big_endian: bool)
+                         -> Result<Option<usize>, usize> {
+    let mut position = 0;
+    for property in &element.properties {
+        position = match property.kind {
+            PropertyKind::Scalar(kind) => position + kind.size(),
+            PropertyKind::List(count_kind, item_kind) => {
+                let count = match binary_value(input.get(position..).unwrap_or(&[]),
+                                               count_kind,
+                                               big_endian) {
+                    IResult::Done(_, count) => list_length(count).ok_or(position)?,
+                    IResult::Error(_) => return Err(position),
+                    IResult::Incomplete(_) => return Ok(None),
+                };
+                let items = count.saturating_mul(item_kind.size());
+                (position + count_kind.size()).saturating_add(items)
+            }
+        };
+    }
+    if position > input.len() {
+        return Ok(None);
+    }
+    Ok(Some(position))
+}
+
+/// Like 'decode_record', but only returns the number of bytes the record takes. Binary records
+/// are measured without decoding their values, the tokens of ASCII records are counted but not
+/// parsed, except for list counts.
+pub fn skip_record(input: &[u8],
+                   offset: usize,
+                   header: &Header,
+                   index: usize)
+                   -> Result<Option<usize>, PlyError> {
+    let big_endian = match header.format.kind {
+        FormatKind::Ascii => {
+            let mut rest = input;
+            for property in &header.elements[index].properties {
+                let skipped = skip_property(rest, &header.format.kind, &property.kind);
+                match decoded(rest, offset + input.len() - rest.len(), index, skipped)? {
+                    Some((remaining, ())) => rest = remaining,
+                    None => return Ok(None),
+                }
+            }
+            return Ok(Some(input.len() - rest.len()));
+        }
+        FormatKind::BigEndian => true,
+        FormatKind::LittleEndian => false,
+    };
+    binary_record_len(input, &header.elements[index], big_endian).map_err(|position| {
+        PlyError::InvalidNumber {
+            offset: offset + position,
+            element: index,
+        }
+    })
+}
+
+/// Checks that 'rest', found at file position 'offset' after the last record, is only whitespace.
+pub fn check_trailing(rest: &[u8], offset: usize, header: &Header) -> Result<(), PlyError> {
+    match rest.iter().position(|&c| !is_space(c) && c != b'\r' && c != b'\n') {
+        Some(extra) => {
+            Err(PlyError::CountMismatch {
+                offset: offset + extra,
+                element: header.elements.len() - 1,
+            })
+        }
+        None => Ok(()),
+    }
+}
+
+/// Decodes all records of the element at 'index' from 'input', which starts at file position
+/// 'offset'. Returns the input after the last record.
+pub fn decode_element<'a>(input: &'a [u8],
+                          offset: usize,
+                          header: &Header,
+                          index: usize)
+                          -> Result<(&'a [u8], Vec<Record>), PlyError> {
+    let offset_of = |remaining: &[u8]| offset + input.len() - remaining.len();
+    let mut rest = input;
+    let count = header.elements[index].count;
+    let mut records = Vec::with_capacity((count as usize).min(rest.len()));
+    for _ in 0..count {
+        match decode_record(rest, offset_of(rest), header, index)? {
+            Some((remaining, record)) => {
+                rest = remaining;
+                records.push(record);
+            }
+            None => {
+                return Err(PlyError::TruncatedBody {
+                    offset: offset_of(rest),
+                    element: index,
+                })
+            }
+        }
+    }
+    Ok((rest, records))
+}
+
+/// Decodes all records of all elements. 'offset' is the position of 'input' in the file and is
+/// only used for error reporting. Trailing whitespace after the last record is ignored.
+pub fn body(input: &[u8],
+            offset: usize,
+            header: &Header)
+            -> Result<HashMap<String, Vec<Record>>, PlyError> {
+    let offset_of = |remaining: &[u8]| offset + input.len() - remaining.len();
+    let mut rest = input;
+    let mut payload = HashMap::new();
+    for (index, element) in header.elements.iter().enumerate() {
+        // The 'count' entry defines how many records of property entries are coming now.
+        let (remaining, records) = decode_element(rest, offset_of(rest), header, index)?;
+        rest = remaining;
+        payload.insert(element.name.clone(), records);
+    }
+    check_trailing(rest, offset_of(rest), header)?;
+    Ok(payload)
+}
+
+#[test]
+fn ascii_value_test() {
+    assert_eq!(IResult::Done(&b"12"[..], Value::UInt8(255)),
+               ascii_value(b"255 12", ValueKind::UInt8));
+    assert_eq!(IResult::Done(&b""[..], Value::Int16(-300)),
+               ascii_value(b"-300\n", ValueKind::Int16));
+    assert_eq!(IResult::Done(&b""[..], Value::UInt64(18446744073709551615)),
+               ascii_value(b"18446744073709551615", ValueKind::UInt64));
+    assert_eq!(IResult::Done(&b""[..], Value::Float64(-0.5)),
+               ascii_value(b"-0.5\r\n", ValueKind::Float64));
+    // Rounded once, straight to the nearest 'f32'.
+    assert_eq!(IResult::Done(&b""[..], Value::Float32(1.0000001)),
+               ascii_value(b"1.00000005960464477539062500000001", ValueKind::Float32));
+    assert_eq!(IResult::Done(&b""[..], Value::Float32(f32::NEG_INFINITY)),
+               ascii_value(b"-inf", ValueKind::Float32));
+    assert_eq!(IResult::Done(&b""[..], Value::Float64(f64::INFINITY)),
+               ascii_value(b"Infinity", ValueKind::Float64));
+    assert!(matches!(ascii_value(b"nan", ValueKind::Float64),
+                     IResult::Done(_, Value::Float64(value)) if value.is_nan()));
+}
+
+#[test]
+fn ascii_value_out_of_range_test() {
+    assert!(ascii_value(b"256", ValueKind::UInt8).is_err());
+    assert!(ascii_value(b"-1", ValueKind::UInt32).is_err());
+    assert!(ascii_value(b"1e39", ValueKind::Float32).is_err());
+    assert!(ascii_value(b"1e400", ValueKind::Float64).is_err());
+    assert!(ascii_value(b"0.5", ValueKind::Int32).is_err());
+    assert!(ascii_value(b"abc", ValueKind::Float32).is_err());
+}
+
+#[test]
+fn binary_value_test() {
+    let input = [0x3f, 0x80, 0x00, 0x00, 0xff];
+    assert_eq!(IResult::Done(&input[4..], Value::Float32(1.)),
+               value(&input, &FormatKind::BigEndian, ValueKind::Float32));
+    assert_eq!(IResult::Done(&input[2..], Value::UInt16(0x803f)),
+               value(&input, &FormatKind::LittleEndian, ValueKind::UInt16));
+    assert_eq!(IResult::Done(&input[1..], Value::Int8(63)),
+               value(&input, &FormatKind::LittleEndian, ValueKind::Int8));
+    assert!(value(&input[..3], &FormatKind::LittleEndian, ValueKind::Int32).is_incomplete());
+}
+
+#[test]
+fn list_value_test() {
+    let kind = PropertyKind::List(ValueKind::UInt8, ValueKind::Int32);
+    assert_eq!(IResult::Done(&b"7"[..],
+                             PropertyValue::List(vec![Value::Int32(1), Value::Int32(-2)])),
+               property_value(b"2 1 -2 7", &FormatKind::Ascii, &kind));
+    assert_eq!(IResult::Done(&b""[..], PropertyValue::List(vec![])),
+               property_value(b"0\n", &FormatKind::Ascii, &kind));
+
+    let input = [0x00, 0x02, 0x01, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff];
+    let kind = PropertyKind::List(ValueKind::UInt16, ValueKind::Int32);
+    assert_eq!(IResult::Done(&b""[..],
+                             PropertyValue::List(vec![Value::Int32(16777216), Value::Int32(-1)])),
+               property_value(&input, &FormatKind::BigEndian, &kind));
+    assert!(property_value(&input[..9], &FormatKind::BigEndian, &kind).is_incomplete());
+
+    let kind = PropertyKind::List(ValueKind::Int8, ValueKind::Int32);
+    assert!(property_value(b"-1 3", &FormatKind::Ascii, &kind).is_err());
+}
@@ -0,0 +1,378 @@
+use body::{binary_value, decode_record, list_length, PropertyValue, Record, Value};
+use error::PlyError;
+use header::{Element, FormatKind, Header, PropertyKind, ValueKind};
+use nom::IResult;
+use std::convert::TryInto;
+use std::mem::size_of;
+
+/// The values of one scalar property, or the items of one list property, of all records.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Int8(Vec<i8>),
+    UInt8(Vec<u8>),
+    Int16(Vec<i16>),
+    UInt16(Vec<u16>),
+    Int32(Vec<i32>),
+    UInt32(Vec<u32>),
+    Int64(Vec<i64>),
+    UInt64(Vec<u64>),
+    Float32(Vec<f32>),
+    Float64(Vec<f64>),
+}
+
+impl Column {
+    fn new(kind: ValueKind) -> Column {
+        match kind {
+            ValueKind::Int8 => Column::Int8(Vec::new()),
+            ValueKind::UInt8 => Column::UInt8(Vec::new()),
+            ValueKind::Int16 => Column::Int16(Vec::new()),
+            ValueKind::UInt16 => Column::UInt16(Vec::new()),
+            ValueKind::Int32 => Column::Int32(Vec::new()),
+            ValueKind::UInt32 => Column::UInt32(Vec::new()),
+            ValueKind::Int64 => Column::Int64(Vec::new()),
+            ValueKind::UInt64 => Column::UInt64(Vec::new()),
+            ValueKind::Float32 => Column::Float32(Vec::new()),
+            ValueKind::Float64 => Column::Float64(Vec::new()),
+        }
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        match *self {
+            Column::Int8(_) => ValueKind::Int8,
+            Column::UInt8(_) => ValueKind::UInt8,
+            Column::Int16(_) => ValueKind::Int16,
+            Column::UInt16(_) => ValueKind::UInt16,
+            Column::Int32(_) => ValueKind::Int32,
+            Column::UInt32(_) => ValueKind::UInt32,
+            Column::Int64(_) => ValueKind::Int64,
+            Column::UInt64(_) => ValueKind::UInt64,
+            Column::Float32(_) => ValueKind::Float32,
+            Column::Float64(_) => ValueKind::Float64,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match *self {
+            Column::Int8(ref values) => values.len(),
+            Column::UInt8(ref values) => values.len(),
+            Column::Int16(ref values) => values.len(),
+            Column::UInt16(ref values) => values.len(),
+            Column::Int32(ref values) => values.len(),
+            Column::UInt32(ref values) => values.len(),
+            Column::Int64(ref values) => values.len(),
+            Column::UInt64(ref values) => values.len(),
+            Column::Float32(ref values) => values.len(),
+            Column::Float64(ref values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The values as a slice of 'T', if 'T' is the type of the column.
+    pub fn as_slice<T: Scalar>(&self) -> Option<&[T]> {
+        T::slice(self)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        match *self {
+            Column::Int8(ref mut values) => values.truncate(len),
+            Column::UInt8(ref mut values) => values.truncate(len),
+            Column::Int16(ref mut values) => values.truncate(len),
+            Column::UInt16(ref mut values) => values.truncate(len),
+            Column::Int32(ref mut values) => values.truncate(len),
+            Column::UInt32(ref mut values) => values.truncate(len),
+            Column::Int64(ref mut values) => values.truncate(len),
+            Column::UInt64(ref mut values) => values.truncate(len),
+            Column::Float32(ref mut values) => values.truncate(len),
+            Column::Float64(ref mut values) => values.truncate(len),
+        }
+    }
+
+    /// Appends 'value', which has the type of the column as it was decoded for it.
+    fn push(&mut self, value: Value) {
+        match (self, value) {
+            (&mut Column::Int8(ref mut values), Value::Int8(v)) => values.push(v),
+            (&mut Column::UInt8(ref mut values), Value::UInt8(v)) => values.push(v),
+            (&mut Column::Int16(ref mut values), Value::Int16(v)) => values.push(v),
+            (&mut Column::UInt16(ref mut values), Value::UInt16(v)) => values.push(v),
+            (&mut Column::Int32(ref mut values), Value::Int32(v)) => values.push(v),
+            (&mut Column::UInt32(ref mut values), Value::UInt32(v)) => values.push(v),
+            (&mut Column::Int64(ref mut values), Value::Int64(v)) => values.push(v),
+            (&mut Column::UInt64(ref mut values), Value::UInt64(v)) => values.push(v),
+            (&mut Column::Float32(ref mut values), Value::Float32(v)) => values.push(v),
+            (&mut Column::Float64(ref mut values), Value::Float64(v)) => values.push(v),
+            (column, value) => unreachable!("{:?} decoded for a {:?} column", value, column.kind()),
+        }
+    }
+
+    /// Appends all values in 'bytes', which holds a whole number of binary encoded values.
+    fn extend_binary(&mut self, bytes: &[u8], big_endian: bool) {
+        fn extend<T: Scalar>(values: &mut Vec<T>, bytes: &[u8], big_endian: bool) {
+            let size = size_of::<T>();
+            values.extend(bytes.chunks(size).map(|chunk| T::from_bytes(chunk, big_endian)));
+        }
+        match *self {
+            Column::Int8(ref mut values) => extend(values, bytes, big_endian),
+            Column::UInt8(ref mut values) => values.extend_from_slice(bytes),
+            Column::Int16(ref mut values) => extend(values, bytes, big_endian),
+            Column::UInt16(ref mut values) => extend(values, bytes, big_endian),
+            Column::Int32(ref mut values) => extend(values, bytes, big_endian),
+            Column::UInt32(ref mut values) => extend(values, bytes, big_endian),
+            Column::Int64(ref mut values) => extend(values, bytes, big_endian),
+            Column::UInt64(ref mut values) => extend(values, bytes, big_endian),
+            Column::Float32(ref mut values) => extend(values, bytes, big_endian),
+            Column::Float64(ref mut values) => extend(values, bytes, big_endian),
+        }
+    }
+}
+
+/// The Rust types that correspond to a 'ValueKind': 'i8', 'u8', 'i16', 'u16', 'i32', 'u32',
+/// 'i64', 'u64', 'f32' and 'f64'.
+pub trait Scalar: Copy {
+    const KIND: ValueKind;
+
+    /// Decodes a value from exactly 'size_of::<Self>()' bytes.
+    #[doc(hidden)]
+    fn from_bytes(bytes: &[u8], big_endian: bool) -> Self;
+
+    #[doc(hidden)]
+    fn slice(column: &Column) -> Option<&[Self]>;
+}
+
+macro_rules! scalar {
+    ($($t:ty: $kind:ident),*) => {
+        $(
+            impl Scalar for $t {
+                const KIND: ValueKind = ValueKind::$kind;
+
+                fn from_bytes(bytes: &[u8], big_endian: bool) -> Self {
+                    let bytes = bytes.try_into().expect("exactly one value");
+                    if big_endian {
+                        <$t>::from_be_bytes(bytes)
+                    } else {
+                        <$t>::from_le_bytes(bytes)
+                    }
+                }
+
+                fn slice(column: &Column) -> Option<&[Self]> {
+                    match *column {
+                        Column::$kind(ref values) => Some(values),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    }
+}
+
+scalar!(i8: Int8, u8: UInt8, i16: Int16, u16: UInt16, i32: Int32, u32: UInt32, i64: Int64,
+        u64: UInt64, f32: Float32, f64: Float64);
+
+/// All values of one property, mirroring 'PropertyKind'.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyColumn {
+    Scalar(Column),
+    /// The items of record 'i' are 'values[offsets[i]..offsets[i + 1]]', 'offsets[0]' is 0.
+    List { offsets: Vec<usize>, values: Column },
+}
+
+impl PropertyColumn {
+    fn new(kind: &PropertyKind) -> PropertyColumn {
+        match *kind {
+            PropertyKind::Scalar(kind) => PropertyColumn::Scalar(Column::new(kind)),
+            PropertyKind::List(_, kind) => {
+                PropertyColumn::List {
+                    offsets: vec![0],
+                    values: Column::new(kind),
+                }
+            }
+        }
+    }
+
+    fn truncate(&mut self, records: usize) {
+        match *self {
+            PropertyColumn::Scalar(ref mut column) => column.truncate(records),
+            PropertyColumn::List { ref mut offsets, ref mut values } => {
+                offsets.truncate(records + 1);
+                values.truncate(offsets[records]);
+            }
+        }
+    }
+}
+
+/// The records of an element stored property by property, see 'ElementReader::read_columns'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Columns {
+    /// The property names, in the order of 'Element::properties' and 'columns'.
+    pub names: Vec<String>,
+    pub columns: Vec<PropertyColumn>,
+    len: usize,
+}
+
+impl Columns {
+    /// Empty columns for the properties of 'element'.
+    pub fn new(element: &Element) -> Columns {
+        Columns {
+            names: element.properties.iter().map(|property| property.name.clone()).collect(),
+            columns: element.properties
+                .iter()
+                .map(|property| PropertyColumn::new(&property.kind))
+                .collect(),
+            len: 0,
+        }
+    }
+
+    /// The number of records.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The column of the property 'name'.
+    pub fn get(&self, name: &str) -> Option<&PropertyColumn> {
+        self.names.iter().position(|n| n == name).map(|index| &self.columns[index])
+    }
+
+    /// The values of the scalar property 'name', if it exists and has type 'T'.
+    pub fn scalar<T: Scalar>(&self, name: &str) -> Option<&[T]> {
+        match self.get(name) {
+            Some(PropertyColumn::Scalar(column)) => column.as_slice(),
+            _ => None,
+        }
+    }
+
+    fn push(&mut self, record: Record) {
+        for (column, value) in self.columns.iter_mut().zip(record) {
+            match (column, value) {
+                (&mut PropertyColumn::Scalar(ref mut column), PropertyValue::Scalar(value)) => {
+                    column.push(value)
+                }
+                (&mut PropertyColumn::List { ref mut offsets, ref mut values },
+                 PropertyValue::List(items)) => {
+                    for item in items {
+                        values.push(item);
+                    }
+                    offsets.push(values.len());
+                }
+                _ => unreachable!("records are decoded for the element"),
+            }
+        }
+        self.len += 1;
+    }
+
+    fn truncate(&mut self, records: usize) {
+        for column in &mut self.columns {
+            column.truncate(records);
+        }
+        self.len = records;
+    }
+}
+
+/// Appends the next binary record in 'input' to 'columns' without going through 'Value'.
+/// Returns the number of bytes consumed, 'None' if 'input' ends early, in which case part of the
+/// record may have been appended, or the position of an invalid list count.
+fn binary_record(input: &[u8],
+                 element: &Element,
+                 big_endian: bool,
+                 columns: &mut Columns)
+                 -> Result<Option<usize>, usize> {
+    let mut position = 0;
+    for (property, column) in element.properties.iter().zip(&mut columns.columns) {
+        match (&property.kind, column) {
+            (&PropertyKind::Scalar(kind), &mut PropertyColumn::Scalar(ref mut column)) => {
+                let end = position + kind.size();
+                if end > input.len() {
+                    return Ok(None);
+                }
+                column.extend_binary(&input[position..end], big_endian);
+                position = end;
+            }
+            (&PropertyKind::List(count_kind, item_kind),
+             &mut PropertyColumn::List { ref mut offsets, ref mut values }) => {
+                let count = match binary_value(&input[position..], count_kind, big_endian) {
+                    IResult::Done(_, count) => list_length(count).ok_or(position)?,
+                    IResult::Error(_) => return Err(position),
+                    IResult::Incomplete(_) => return Ok(None),
+                };
+                position += count_kind.size();
+                let end = position.saturating_add(count.saturating_mul(item_kind.size()));
+                if end > input.len() {
+                    return Ok(None);
+                }
+                values.extend_binary(&input[position..end], big_endian);
+                offsets.push(values.len());
+                position = end;
+            }
+            _ => unreachable!("columns are created for the element"),
+        }
+    }
+    columns.len += 1;
+    Ok(Some(position))
+}
+
+/// Decodes the next record of the element at 'index' from 'input', which starts at file
+/// position 'offset', and appends it to 'columns'. Returns the number of bytes consumed or
+/// 'None' if 'input' ends before the record is complete.
+pub fn decode_columns(input: &[u8],
+                      offset: usize,
+                      header: &Header,
+                      index: usize,
+                      columns: &mut Columns)
+                      -> Result<Option<usize>, PlyError> {
+    let big_endian = match header.format.kind {
+        FormatKind::Ascii => {
+            return Ok(decode_record(input, offset, header, index)?.map(|(rest, record)| {
+                columns.push(record);
+                input.len() - rest.len()
+            }))
+        }
+        FormatKind::BigEndian => true,
+        FormatKind::LittleEndian => false,
+    };
+    let records = columns.len;
+    match binary_record(input, &header.elements[index], big_endian, columns) {
+        Ok(Some(consumed)) => Ok(Some(consumed)),
+        Ok(None) => {
+            columns.truncate(records);
+            Ok(None)
+        }
+        Err(position) => {
+            Err(PlyError::InvalidNumber {
+                offset: offset + position,
+                element: index,
+            })
+        }
+    }
+}
+
+#[test]
+fn decode_columns_test() {
+    use header::header;
+
+    let input = b"ply\nformat binary_big_endian 1.0\nelement vertex 2\nproperty float x\n\
+                  property list uchar ushort i\nend_header\n\
+                  \x3f\x80\x00\x00\x02\x00\x01\x00\x02\
+                  \x40\x00\x00\x00\x00\
+                  \x40\x40\x00\x00\x01\x00";
+    let (body, header) = header(input, &Default::default()).unwrap();
+    let mut columns = Columns::new(&header.elements[0]);
+    let mut position = 0;
+    while let Some(consumed) = decode_columns(&body[position..], 0, &header, 0, &mut columns)
+        .unwrap() {
+        position += consumed;
+    }
+    // The third record is incomplete and must not leave anything behind.
+    assert_eq!(2, columns.len());
+    assert_eq!(Some(&[1.0f32, 2.0][..]), columns.scalar::<f32>("x"));
+    assert_eq!(None, columns.scalar::<f64>("x"));
+    assert_eq!(Some(&PropertyColumn::List {
+                   offsets: vec![0, 2, 2],
+                   values: Column::UInt16(vec![1, 2]),
+               }),
+               columns.get("i"));
+}
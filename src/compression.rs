@@ -0,0 +1,163 @@
+use body::Ply;
+use error::PlyError;
+use std::fmt;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+#[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+use writer::write;
+#[cfg(feature = "gzip")]
+use flate2::read::MultiGzDecoder;
+#[cfg(feature = "gzip")]
+use flate2::write::GzEncoder;
+#[cfg(feature = "xz")]
+use xz2::read::XzDecoder;
+#[cfg(feature = "xz")]
+use xz2::write::XzEncoder;
+
+/// A compression format PLY files are commonly stored in. Each is only supported with the cargo
+/// feature of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// The compression of data starting with 'magic', 'None' if it is not compressed in any of
+    /// the known formats. Only the first six bytes are looked at.
+    pub fn detect(magic: &[u8]) -> Option<Compression> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Xz => write!(f, "xz"),
+        }
+    }
+}
+
+/// Looks at the first bytes of 'reader' and wraps it in a decompressor if they are the magic of
+/// a known compression format, so that the result always starts with the plain PLY file. Fails
+/// with 'PlyError::UnsupportedCompression' if the feature for the format is not enabled.
+///
+/// 'read' does this by itself, wrap the input of a 'Reader' with it to stream compressed files.
+pub fn decompress<'a, R: Read + 'a>(mut reader: R) -> Result<Box<dyn BufRead + 'a>, PlyError> {
+    let mut magic = [0; 6];
+    let mut len = 0;
+    while len < magic.len() {
+        match reader.read(&mut magic[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+    let compression = Compression::detect(&magic[..len]);
+    // Put the magic back in front of the rest of the input.
+    let input = Cursor::new(magic).take(len as u64).chain(reader);
+    Ok(match compression {
+        None => Box::new(BufReader::new(input)),
+        #[cfg(feature = "gzip")]
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(input))),
+        #[cfg(feature = "zstd")]
+        Some(Compression::Zstd) => Box::new(BufReader::new(::zstd::Decoder::new(input)?)),
+        #[cfg(feature = "xz")]
+        Some(Compression::Xz) => Box::new(BufReader::new(XzDecoder::new_multi_decoder(input))),
+        #[allow(unreachable_patterns)]
+        Some(compression) => return Err(PlyError::UnsupportedCompression { compression }),
+    })
+}
+
+/// Like 'write', but compresses the output with 'compression'. Fails with
+/// 'PlyError::UnsupportedCompression' if the feature for the format is not enabled.
+pub fn write_compressed<W: Write>(writer: W,
+                                  ply: &Ply,
+                                  compression: Compression)
+                                  -> Result<(), PlyError> {
+    match compression {
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(writer, ::flate2::Compression::default());
+            write(&mut encoder, ply)?;
+            encoder.finish()?;
+            Ok(())
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            let mut encoder = ::zstd::Encoder::new(writer, 0)?;
+            write(&mut encoder, ply)?;
+            encoder.finish()?;
+            Ok(())
+        }
+        #[cfg(feature = "xz")]
+        Compression::Xz => {
+            let mut encoder = XzEncoder::new(writer, 6);
+            write(&mut encoder, ply)?;
+            encoder.finish()?;
+            Ok(())
+        }
+        #[allow(unreachable_patterns)]
+        compression => {
+            // Without any compression feature these are not used otherwise.
+            let _ = (writer, ply);
+            Err(PlyError::UnsupportedCompression { compression })
+        }
+    }
+}
+
+#[test]
+fn detect_test() {
+    assert_eq!(Some(Compression::Gzip), Compression::detect(&[0x1f, 0x8b, 8]));
+    assert_eq!(Some(Compression::Zstd), Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0]));
+    assert_eq!(Some(Compression::Xz), Compression::detect(b"\xfd7zXZ\x00"));
+    assert_eq!(None, Compression::detect(b"\xfd7zX"));
+    assert_eq!(None, Compression::detect(b"ply\n"));
+}
+
+#[test]
+fn decompress_plain_test() {
+    let mut input = String::new();
+    decompress(&b"ply\n"[..]).unwrap().read_to_string(&mut input).unwrap();
+    assert_eq!("ply\n", input);
+    #[cfg(not(feature = "gzip"))]
+    assert!(matches!(decompress(&[0x1f, 0x8b][..]),
+                     Err(PlyError::UnsupportedCompression { compression: Compression::Gzip })));
+}
+
+#[cfg(all(test, any(feature = "gzip", feature = "zstd", feature = "xz")))]
+fn round_trip(compression: Compression) {
+    let ply = ::read(::std::fs::File::open("testdata/beethoven.ply").unwrap()).unwrap();
+    let mut output = Vec::new();
+    write_compressed(&mut output, &ply, compression).unwrap();
+    assert_eq!(Some(compression), Compression::detect(&output));
+    assert_eq!(ply, ::read(&output[..]).unwrap());
+    assert_eq!(ply, ::parse(&output).unwrap());
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_test() {
+    round_trip(Compression::Gzip);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_test() {
+    round_trip(Compression::Zstd);
+}
+
+#[cfg(feature = "xz")]
+#[test]
+fn xz_test() {
+    round_trip(Compression::Xz);
+}
@@ -0,0 +1,216 @@
+use body::{PropertyValue, Record, Value};
+use error::PlyError;
+use header::{Element, Property};
+use serde::de::value::{SeqDeserializer, StrDeserializer};
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess,
+                Visitor};
+use std::iter::Zip;
+use std::slice;
+use std::vec;
+
+impl de::Error for PlyError {
+    fn custom<T: ::std::fmt::Display>(msg: T) -> Self {
+        PlyError::Serde(msg.to_string())
+    }
+}
+
+struct ValueDeserializer(Value);
+
+impl<'de> IntoDeserializer<'de, PlyError> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer {
+    type Error = PlyError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlyError> {
+        match self.0 {
+            Value::Int8(v) => visitor.visit_i8(v),
+            Value::UInt8(v) => visitor.visit_u8(v),
+            Value::Int16(v) => visitor.visit_i16(v),
+            Value::UInt16(v) => visitor.visit_u16(v),
+            Value::Int32(v) => visitor.visit_i32(v),
+            Value::UInt32(v) => visitor.visit_u32(v),
+            Value::Int64(v) => visitor.visit_i64(v),
+            Value::UInt64(v) => visitor.visit_u64(v),
+            Value::Float32(v) => visitor.visit_f32(v),
+            Value::Float64(v) => visitor.visit_f64(v),
+        }
+    }
+
+    // A property that is present is never 'None'.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlyError> {
+        visitor.visit_some(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct PropertyValueDeserializer(PropertyValue);
+
+impl<'de> Deserializer<'de> for PropertyValueDeserializer {
+    type Error = PlyError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlyError> {
+        match self.0 {
+            PropertyValue::Scalar(value) => ValueDeserializer(value).deserialize_any(visitor),
+            PropertyValue::List(values) => {
+                let mut items = SeqDeserializer::new(values.into_iter().map(ValueDeserializer));
+                let result = visitor.visit_seq(&mut items)?;
+                items.end()?;
+                Ok(result)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlyError> {
+        visitor.visit_some(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// A record seen as a map from property names to values, or as a sequence of values in
+/// declaration order.
+struct RecordDeserializer<'a> {
+    values: Zip<slice::Iter<'a, Property>, vec::IntoIter<PropertyValue>>,
+    value: Option<PropertyValue>,
+}
+
+impl<'de, 'a> MapAccess<'de> for RecordDeserializer<'a> {
+    type Error = PlyError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self,
+                                              seed: K)
+                                              -> Result<Option<K::Value>, PlyError> {
+        match self.values.next() {
+            Some((property, value)) => {
+                self.value = Some(value);
+                let key: StrDeserializer<PlyError> = property.name.as_str().into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, PlyError> {
+        let value = self.value.take().expect("next_value_seed is called after next_key_seed");
+        seed.deserialize(PropertyValueDeserializer(value))
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for RecordDeserializer<'a> {
+    type Error = PlyError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, PlyError> {
+        visitor.visit_map(&mut self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlyError> {
+        let values = self.values.map(|(_, value)| PropertyValueDeserializer(value));
+        let mut items = SeqDeserializer::new(values);
+        let result = visitor.visit_seq(&mut items)?;
+        items.end()?;
+        Ok(result)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self,
+                                          _len: usize,
+                                          visitor: V)
+                                          -> Result<V::Value, PlyError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self,
+                                                 _name: &'static str,
+                                                 _len: usize,
+                                                 visitor: V)
+                                                 -> Result<V::Value, PlyError> {
+        self.deserialize_seq(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, PlyError> for PropertyValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserializes 'record', a record of 'element', into any 'T'. Structs and maps see the
+/// properties by name, tuples and sequences see the values in declaration order. Lists are
+/// sequences.
+pub fn from_record<'de, T: Deserialize<'de>>(element: &Element,
+                                             record: Record)
+                                             -> Result<T, PlyError> {
+    T::deserialize(RecordDeserializer {
+        values: element.properties.iter().zip(record),
+        value: None,
+    })
+}
+
+#[cfg(test)]
+pub fn test_element() -> Element {
+    use header::{PropertyKind, ValueKind};
+    Element {
+        name: "vertex".to_string(),
+        count: 1,
+        properties: vec![Property {
+                             name: "x".to_string(),
+                             kind: PropertyKind::Scalar(ValueKind::Float32),
+                             line: 0,
+                         },
+                         Property {
+                             name: "indices".to_string(),
+                             kind: PropertyKind::List(ValueKind::UInt8, ValueKind::UInt8),
+                             line: 0,
+                         }],
+        line: 0,
+    }
+}
+
+#[test]
+fn from_record_test() {
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Vertex {
+        x: f64,
+        indices: Vec<u32>,
+        missing: Option<u8>,
+    }
+
+    let record = || {
+        vec![PropertyValue::Scalar(Value::Float32(0.5)),
+             PropertyValue::List(vec![Value::UInt8(1), Value::UInt8(2)])]
+    };
+    assert_eq!(Vertex { x: 0.5, indices: vec![1, 2], missing: None },
+               from_record::<Vertex>(&test_element(), record()).unwrap());
+    assert_eq!((0.5f32, vec![1u8, 2]),
+               from_record::<(f32, Vec<u8>)>(&test_element(), record()).unwrap());
+    let map = from_record::<BTreeMap<String, Vec<i16>>>(&test_element(),
+                                                         vec![PropertyValue::List(vec![]),
+                                                              PropertyValue::List(vec![])]);
+    assert_eq!(2, map.unwrap().len());
+    // A float is not silently truncated to an integer.
+    let record = vec![PropertyValue::Scalar(Value::Float32(0.5)),
+                      PropertyValue::List(vec![Value::UInt8(1)])];
+    assert!(matches!(from_record::<(u8, Vec<u8>)>(&test_element(), record),
+                     Err(PlyError::Serde(_))));
+}
@@ -0,0 +1,176 @@
+use compression::Compression;
+use std::error::Error;
+use std::fmt;
+use header::Version;
+use std::io;
+use validate::Diagnostic;
+
+/// Everything that can go wrong while reading or writing a PLY file.
+///
+/// Header errors carry the 1-based line number in the header, body errors carry the byte offset
+/// from the start of the file and the index of the element in 'Header::elements' being read.
+#[derive(Debug)]
+pub enum PlyError {
+    /// Reading from the underlying reader failed.
+    Io(io::Error),
+    /// The input does not start with the 'ply' magic.
+    BadMagic,
+    /// The 'format' line is missing, repeated, malformed or names an unknown format.
+    BadFormat { line: usize },
+    /// The 'format' line names a version other than 1.0 and 'ReadOptions::allow_unknown_versions'
+    /// is not set.
+    UnsupportedVersion { line: usize, version: Version },
+    /// A property declares a type that is not known, 'name' is the unknown type name.
+    UnknownType { line: usize, name: String },
+    /// A header line is malformed, unknown or out of place.
+    BadHeaderLine { line: usize },
+    /// 'Header::validate' found these errors in an otherwise well-formed header. Without strict
+    /// mode only duplicate element names are rejected, a 'Ply' cannot hold their records.
+    InvalidHeader(Vec<Diagnostic>),
+    /// The input ended before 'end_header'.
+    TruncatedHeader { line: usize },
+    /// The input ended before all records declared in the header were read.
+    TruncatedBody { offset: usize, element: usize },
+    /// The body contains more data than the element counts in the header declare.
+    CountMismatch { offset: usize, element: usize },
+    /// A value in the body is malformed, out of range or a list count is negative.
+    InvalidNumber { offset: usize, element: usize },
+    /// A property of the element cannot be converted to the type it is read into.
+    IncompatibleProperty { element: usize, property: String },
+    /// A record could not be deserialized into or serialized from a serde type. Only returned
+    /// with the 'serde' feature, but always declared so that enabling it does not change the enum.
+    Serde(String),
+    /// The records of the element have no fixed size, because it has list properties or the
+    /// file is ASCII, so there is no strided view of it.
+    NotFixedSize { element: usize },
+    /// The header declares no element of this name.
+    NoSuchElement { name: String },
+    /// The element at this index declares no property of this name.
+    NoSuchProperty { element: usize, name: String },
+    /// An 'Index' is malformed or was built for a different file.
+    BadIndex,
+    /// The input or output is compressed with a format whose cargo feature is not enabled.
+    UnsupportedCompression { compression: Compression },
+    /// The writer cannot write this element or property name, it is empty or contains whitespace.
+    BadName { name: String },
+    /// The writer cannot write this comment or obj_info, it contains a line break or starts with
+    /// whitespace.
+    BadComment { text: String },
+    /// The writer cannot write the element at this index, its count is negative.
+    BadCount { element: usize, count: i64 },
+    /// The writer cannot write this format version, it is negative.
+    BadVersion { version: Version },
+    /// Data passed to the writer does not match the header: a record is missing or superfluous,
+    /// or one of its values does not have the declared type.
+    MismatchedRecord { element: usize, record: usize },
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlyError::Io(ref err) => write!(f, "I/O error: {}", err),
+            PlyError::BadMagic => write!(f, "not a PLY file, missing 'ply' magic"),
+            PlyError::BadFormat { line } => write!(f, "line {}: invalid format line", line),
+            PlyError::UnsupportedVersion { line, version } => {
+                write!(f, "line {}: unsupported format version {}", line, version)
+            }
+            PlyError::UnknownType { line, ref name } => {
+                write!(f, "line {}: unknown property type '{}'", line, name)
+            }
+            PlyError::BadHeaderLine { line } => write!(f, "line {}: invalid header line", line),
+            PlyError::InvalidHeader(ref errors) => {
+                write!(f, "invalid header")?;
+                for (index, error) in errors.iter().enumerate() {
+                    write!(f, "{} {}", if index == 0 { ":" } else { ";" }, error)?;
+                }
+                Ok(())
+            }
+            PlyError::TruncatedHeader { line } => {
+                write!(f, "line {}: unexpected end of header", line)
+            }
+            PlyError::TruncatedBody { offset, element } => {
+                write!(f,
+                       "byte {}: unexpected end of body in element {}",
+                       offset,
+                       element)
+            }
+            PlyError::CountMismatch { offset, element } => {
+                write!(f,
+                       "byte {}: more data than declared after element {}",
+                       offset,
+                       element)
+            }
+            PlyError::InvalidNumber { offset, element } => {
+                write!(f, "byte {}: invalid number in element {}", offset, element)
+            }
+            PlyError::IncompatibleProperty { element, ref property } => {
+                write!(f,
+                       "property '{}' of element {} is missing or has an incompatible type",
+                       property,
+                       element)
+            }
+            PlyError::Serde(ref message) => write!(f, "{}", message),
+            PlyError::NotFixedSize { element } => {
+                write!(f, "records of element {} do not have a fixed size", element)
+            }
+            PlyError::NoSuchElement { ref name } => write!(f, "no element named '{}'", name),
+            PlyError::NoSuchProperty { element, ref name } => {
+                write!(f, "element {} has no property named '{}'", element, name)
+            }
+            PlyError::BadIndex => write!(f, "index is malformed or does not match the file"),
+            PlyError::UnsupportedCompression { compression } => {
+                write!(f, "{} compression requires the '{}' feature", compression, compression)
+            }
+            PlyError::BadName { ref name } => write!(f, "cannot write name '{}'", name),
+            PlyError::BadComment { ref text } => write!(f, "cannot write comment {:?}", text),
+            PlyError::BadCount { element, count } => {
+                write!(f, "cannot write count {} of element {}", count, element)
+            }
+            PlyError::BadVersion { version } => write!(f, "cannot write version {}", version),
+            PlyError::MismatchedRecord { element, record } => {
+                write!(f,
+                       "record {} of element {} does not match the header",
+                       record,
+                       element)
+            }
+        }
+    }
+}
+
+impl Error for PlyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            PlyError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Why a 'Value' could not be converted to a primitive type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The value is out of the range of the target type, e.g. negative for an unsigned type.
+    Overflow,
+    /// The target type cannot represent the value exactly, e.g. a fraction as an integer or a
+    /// large integer as a float.
+    PrecisionLoss,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConversionError::Overflow => write!(f, "value out of range for the target type"),
+            ConversionError::PrecisionLoss => {
+                write!(f, "value cannot be represented exactly in the target type")
+            }
+        }
+    }
+}
+
+impl Error for ConversionError {}
+
+impl From<io::Error> for PlyError {
+    fn from(err: io::Error) -> Self {
+        PlyError::Io(err)
+    }
+}
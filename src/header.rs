@@ -0,0 +1,604 @@
+use error::PlyError;
+use options::ReadOptions;
+use nom::{Err, ErrorKind, IResult, not_line_ending, digit, multispace, space};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::from_utf8;
+use validate::Issue;
+
+/// Custom nom error code for a property whose type names could not be parsed.
+const UNKNOWN_TYPE: u32 = 1;
+/// Custom nom error code for an invalid element or property name.
+const BAD_NAME: u32 = 2;
+
+/// The version given in the 'format' line of the header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Version {
+    pub major: i32,
+    pub minor: i32,
+}
+
+impl Version {
+    /// The only version of the format there is, and the only one accepted by default.
+    pub const SUPPORTED: Version = Version { major: 1, minor: 0 };
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// The encoding of the body.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FormatKind {
+    Ascii,
+    BigEndian,
+    LittleEndian,
+}
+
+/// The 'format' line of the header.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Format {
+    pub kind: FormatKind,
+    pub version: Version,
+}
+
+/// A free text line of the header, either a 'comment' or an 'obj_info' line.
+///
+/// 'line' is the 1-based line number in the header, 'ply' being line 1. It keeps comments and
+/// obj_infos in order relative to each other, and the writer puts them before the first element
+/// or property declared after them, see 'layout'.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Comment {
+    pub line: usize,
+    pub text: String,
+}
+
+/// The header of a PLY file, describing the layout of the body.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Header {
+    pub comments: Vec<Comment>,
+    pub obj_infos: Vec<Comment>,
+    pub format: Format,
+    pub elements: Vec<Element>,
+}
+
+named!(format_version<Version>,
+   chain!(
+       major: map_res!(map_res!(digit, from_utf8), str::parse) ~
+       tag!(".") ~
+       minor: map_res!(map_res!(digit,  from_utf8), str::parse),
+       || Version { minor, major }
+    )
+);
+
+named!(format<Format>,
+   chain!(
+       tag!("format") ~
+       multispace ~
+       kind: alt!(
+           map!(tag!("ascii"), |_| FormatKind::Ascii) |
+           map!(tag!("binary_big_endian"), |_| FormatKind::BigEndian) |
+           map!(tag!("binary_little_endian"), |_| FormatKind::LittleEndian)
+       ) ~
+       multispace ~
+       version: format_version ~
+       multispace,
+       || Format { kind, version }
+    )
+);
+
+/// The type of a scalar value.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValueKind {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+}
+
+impl ValueKind {
+    /// The number of bytes a value takes in a binary body.
+    pub fn size(&self) -> usize {
+        match *self {
+            ValueKind::Int8 | ValueKind::UInt8 => 1,
+            ValueKind::Int16 | ValueKind::UInt16 => 2,
+            ValueKind::Int32 | ValueKind::UInt32 | ValueKind::Float32 => 4,
+            ValueKind::Int64 | ValueKind::UInt64 | ValueKind::Float64 => 8,
+        }
+    }
+}
+
+/// The type of a property, lists carry the type of their count and of their items.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PropertyKind {
+    Scalar(ValueKind),
+    List(ValueKind, ValueKind),
+}
+
+/// A named property of an element.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Property {
+    pub name: String,
+    pub kind: PropertyKind,
+    /// The 1-based header line the property is declared on, 0 if it was not parsed.
+    pub line: usize,
+}
+
+/// An element declaration: 'count' records, each consisting of 'properties'.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub count: i64,
+    pub properties: Vec<Property>,
+    /// The 1-based header line the element is declared on, 0 if it was not parsed.
+    pub line: usize,
+}
+
+impl Element {
+    /// The size of a record in a binary body, 'None' if it has list properties.
+    pub fn record_size(&self) -> Option<usize> {
+        self.properties
+            .iter()
+            .map(|property| match property.kind {
+                PropertyKind::Scalar(kind) => Some(kind.size()),
+                PropertyKind::List(..) => None,
+            })
+            .sum()
+    }
+}
+
+pub fn is_name_char(a: u8) -> bool {
+    !matches!(a, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+pub fn is_strict_name(name: &str) -> bool {
+    name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_') &&
+    !name.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// The name of an element or property, see 'ReadOptions::strict'.
+fn identifier(input: &[u8], strict: bool) -> IResult<&[u8], &str> {
+    match map_res!(input, take_while1!(is_name_char), from_utf8) {
+        IResult::Done(_, name) if name.is_empty() || (strict && !is_strict_name(name)) => {
+            IResult::Error(Err::Position(ErrorKind::Custom(BAD_NAME), input))
+        }
+        other => other,
+    }
+}
+
+/// The type with the given name, all names from the specification and their sized aliases.
+fn value_kind(name: &str) -> Option<ValueKind> {
+    match name {
+        "char" | "int8" => Some(ValueKind::Int8),
+        "uchar" | "uint8" => Some(ValueKind::UInt8),
+        "short" | "int16" => Some(ValueKind::Int16),
+        "ushort" | "uint16" => Some(ValueKind::UInt16),
+        "int" | "int32" => Some(ValueKind::Int32),
+        "uint" | "uint32" => Some(ValueKind::UInt32),
+        "int64" => Some(ValueKind::Int64),
+        "uint64" => Some(ValueKind::UInt64),
+        "float" | "float32" => Some(ValueKind::Float32),
+        "double" | "float64" => Some(ValueKind::Float64),
+        _ => None,
+    }
+}
+
+/// A type name. The whole token must match, so 'int8x' is an unknown type rather than 'int8'
+/// followed by garbage. Unknown types fail with 'UNKNOWN_TYPE' at the start of the token.
+fn data_type(input: &[u8]) -> IResult<&[u8], ValueKind> {
+    let token: IResult<&[u8], &str> = map_res!(input, take_while1!(is_name_char), from_utf8);
+    match token {
+        IResult::Done(rest, name) => {
+            match value_kind(name) {
+                Some(kind) => IResult::Done(rest, kind),
+                None => IResult::Error(Err::Position(ErrorKind::Custom(UNKNOWN_TYPE), input)),
+            }
+        }
+        IResult::Error(_) => IResult::Error(Err::Position(ErrorKind::Custom(UNKNOWN_TYPE), input)),
+        IResult::Incomplete(needed) => IResult::Incomplete(needed),
+    }
+}
+
+// Not an 'alt!', which would swallow the position of an unknown type in a list.
+fn property_kind(input: &[u8]) -> IResult<&[u8], PropertyKind> {
+    match chain!(input, tag!("list") ~ space, || ()) {
+        IResult::Done(rest, _) => {
+            chain!(rest,
+                count_data_type: data_type ~
+                space ~
+                element_data_type: data_type,
+                || PropertyKind::List(count_data_type, element_data_type)
+            )
+        }
+        _ => map!(input, data_type, PropertyKind::Scalar),
+    }
+}
+
+// Tokens of a declaration are separated by 'space', so that it cannot continue on the next line.
+fn property(input: &[u8], strict: bool) -> IResult<&[u8], Property> {
+    chain!(input,
+        tag!("property") ~
+        space ~
+        kind: property_kind ~
+        space ~
+        name: call!(identifier, strict) ~
+        multispace,
+        || Property {
+            name: name.to_string(),
+            kind,
+            line: 0,
+        }
+    )
+}
+
+fn element(input: &[u8], strict: bool) -> IResult<&[u8], Element> {
+    chain!(input,
+        tag!("element") ~
+        space ~
+        name: call!(identifier, strict) ~
+        space ~
+        count: map_res!(map_res!(digit, from_utf8), str::parse) ~
+        multispace,
+        || Element {
+            name: name.to_string(),
+            count,
+            properties: Vec::new(),
+            line: 0,
+        }
+    )
+}
+
+// What may follow the 'comment' and 'obj_info' keywords: the text after spaces, or the end of
+// an empty one.
+// Not 'multispace', an empty comment must not swallow the next line.
+named!(comment_separator,
+    alt!(space | peek!(alt!(tag!("\n") | tag!("\r"))))
+);
+
+named!(comment<String>,
+    chain!(
+        tag!("comment") ~
+        comment_separator ~
+        comment: map_res!(not_line_ending, from_utf8) ~
+        multispace,
+        || comment.to_string()
+    )
+);
+
+named!(obj_info<String>,
+    chain!(
+        tag!("obj_info") ~
+        comment_separator ~
+        obj_info: map_res!(not_line_ending, from_utf8) ~
+        multispace,
+        || obj_info.to_string()
+    )
+);
+
+named!(magic,
+    chain!(
+        tag!("ply") ~
+        multispace,
+        || &b"ply"[..]
+    )
+);
+
+named!(end_header,
+    chain!(
+        tag!("end_header") ~
+        opt!(space) ~
+        // Only a single line ending may follow, for binary formats the body starts right after.
+        alt!(tag!("\n") | tag!("\r\n")),
+        || &b"end_header"[..]
+    )
+);
+
+enum HeaderLine {
+    Format(Format),
+    Comment(String),
+    ObjInfo(String),
+    Element(Element),
+    Property(Property),
+    EndHeader,
+}
+
+fn header_line(input: &[u8], strict: bool) -> IResult<&[u8], HeaderLine> {
+    match alt!(input,
+               map!(end_header, |_| HeaderLine::EndHeader) |
+               map!(format, HeaderLine::Format) |
+               map!(comment, HeaderLine::Comment) |
+               map!(obj_info, HeaderLine::ObjInfo) |
+               map!(call!(element, strict), HeaderLine::Element)) {
+        // Property is tried last and outside of 'alt!' so that its error is not swallowed.
+        IResult::Error(_) => map!(input, call!(property, strict), HeaderLine::Property),
+        other => other,
+    }
+}
+
+/// Where the error with the custom 'code' occurred, if 'err' contains one.
+fn error_at<'a>(err: &Err<&'a [u8]>, code: u32) -> Option<&'a [u8]> {
+    match *err {
+        Err::Position(ref kind, position) if *kind == ErrorKind::Custom(code) => Some(position),
+        Err::NodePosition(ref kind, position, _) if *kind == ErrorKind::Custom(code) => {
+            Some(position)
+        }
+        Err::Node(_, ref next) | Err::NodePosition(_, _, ref next) => error_at(next, code),
+        _ => None,
+    }
+}
+
+/// The 1-based line number at which 'remaining' starts in 'input'.
+fn line_number(input: &[u8], remaining: &[u8]) -> usize {
+    let consumed = &input[..input.len() - remaining.len()];
+    consumed.iter().filter(|&&c| c == b'\n').count() + 1
+}
+
+/// Parses the header, returning the remaining input, i.e. the body, and the header.
+pub fn header<'a>(input: &'a [u8], options: &ReadOptions) -> Result<(&'a [u8], Header), PlyError> {
+    let mut rest = match magic(input) {
+        IResult::Done(rest, _) => rest,
+        IResult::Error(_) => return Err(PlyError::BadMagic),
+        IResult::Incomplete(_) => return Err(PlyError::TruncatedHeader { line: 1 }),
+    };
+    // Comments and obj_infos may come before the format line, so it is handled in the loop.
+    let mut format = None;
+    let mut comments = Vec::new();
+    let mut obj_infos = Vec::new();
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        let line = line_number(input, rest);
+        let header_line = match header_line(rest, options.strict) {
+            IResult::Done(remaining, header_line) => {
+                rest = remaining;
+                header_line
+            }
+            IResult::Error(ref err) if error_at(err, UNKNOWN_TYPE).is_some() => {
+                let position = error_at(err, UNKNOWN_TYPE).expect("checked by the guard");
+                let token = position.iter()
+                    .position(|&c| !is_name_char(c))
+                    .unwrap_or(position.len());
+                let name = String::from_utf8_lossy(&position[..token]).into_owned();
+                return Err(PlyError::UnknownType { line, name });
+            }
+            IResult::Error(_) if rest.starts_with(b"format") => {
+                return Err(PlyError::BadFormat { line })
+            }
+            IResult::Error(_) => return Err(PlyError::BadHeaderLine { line }),
+            IResult::Incomplete(_) => return Err(PlyError::TruncatedHeader { line }),
+        };
+
+        let needs_format = matches!(header_line, HeaderLine::Element(_) | HeaderLine::EndHeader);
+        if needs_format && format.is_none() {
+            return Err(PlyError::BadFormat { line });
+        }
+
+        match header_line {
+            HeaderLine::Format(_) if format.is_some() => return Err(PlyError::BadFormat { line }),
+            HeaderLine::Format(ref f) if f.version != Version::SUPPORTED &&
+                                         !options.allow_unknown_versions => {
+                return Err(PlyError::UnsupportedVersion { line, version: f.version })
+            }
+            HeaderLine::Format(f) => format = Some(f),
+            HeaderLine::Comment(text) => comments.push(Comment { line, text }),
+            HeaderLine::ObjInfo(text) => obj_infos.push(Comment { line, text }),
+            HeaderLine::Element(element) => elements.push(Element { line, ..element }),
+            HeaderLine::Property(property) => {
+                match elements.last_mut() {
+                    Some(element) => element.properties.push(Property { line, ..property }),
+                    None => return Err(PlyError::BadHeaderLine { line }),
+                }
+            }
+            HeaderLine::EndHeader if elements.is_empty() => {
+                return Err(PlyError::BadHeaderLine { line })
+            }
+            HeaderLine::EndHeader => break,
+        }
+    }
+
+    let header = Header {
+        comments,
+        obj_infos,
+        format: format.expect("checked at the first element"),
+        elements,
+    };
+    // A 'Ply' keys its records by element name, so duplicate elements are never accepted.
+    let errors = header.validate()
+        .into_iter()
+        .filter(|diagnostic| {
+            diagnostic.is_error() &&
+            (options.strict || matches!(diagnostic.issue, Issue::DuplicateElement(_)))
+        })
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        return Err(PlyError::InvalidHeader(errors));
+    }
+    Ok((rest, header))
+}
+
+/// One line of a header, see 'layout'.
+pub enum Line<'a> {
+    Magic,
+    Format(&'a Format),
+    Comment(&'a Comment),
+    ObjInfo(&'a Comment),
+    Element(&'a Element),
+    Property(&'a Property),
+    EndHeader,
+}
+
+/// The lines of 'header' in order, line 'n' being at index 'n - 1'. Comments and obj_infos go
+/// right before the first element or property declared on a later line than their
+/// 'Comment::line', so blank lines in a parsed header do not move them. Declarations that were
+/// not parsed count as being on the line they end up on. Nothing goes before the 'format' line,
+/// which other readers expect right after 'ply'.
+pub fn layout<'a>(header: &'a Header) -> Vec<Line<'a>> {
+    let mut comments = header.comments
+        .iter()
+        .map(|comment| (comment.line, Line::Comment(comment)))
+        .chain(header.obj_infos.iter().map(|obj_info| (obj_info.line, Line::ObjInfo(obj_info))))
+        .collect::<Vec<_>>();
+    comments.sort_by_key(|&(line, _)| line);
+    let mut comments = comments.into_iter().peekable();
+
+    let mut lines = vec![Line::Magic, Line::Format(&header.format)];
+    // Adds all comments that come before a declaration on line 'declared', 0 if it was not parsed.
+    let add_comments = |lines: &mut Vec<Line<'a>>, comments: &mut Peekable<_>, declared: usize| {
+        let before = |line: usize, lines: &Vec<Line<'a>>| {
+            if declared == 0 { line <= lines.len() + 1 } else { line < declared }
+        };
+        while let Some((_, comment)) = comments.next_if(|&(line, _)| before(line, lines)) {
+            lines.push(comment);
+        }
+    };
+    for element in &header.elements {
+        add_comments(&mut lines, &mut comments, element.line);
+        lines.push(Line::Element(element));
+        for property in &element.properties {
+            add_comments(&mut lines, &mut comments, property.line);
+            lines.push(Line::Property(property));
+        }
+    }
+    // Whatever is left comes after the last property.
+    lines.extend(comments.map(|(_, comment)| comment));
+    lines.push(Line::EndHeader);
+    lines
+}
+
+#[test]
+fn parse_category_test() {
+    let input = b"property list uint8 int32 vertex_indices\n";
+    let res = property(input, true);
+    if let IResult::Done(_, res) = res {
+        assert_eq!(Property {
+                       kind: PropertyKind::List(ValueKind::UInt8, ValueKind::Int32),
+                       name: "vertex_indices".into(),
+                       line: 0,
+                   },
+                   res);
+    } else {
+        panic!("res: {:?}", res);
+    }
+}
+
+#[test]
+fn data_type_test() {
+    let kinds = |input: &[u8]| match property(input, false) {
+        IResult::Done(_, property) => Some(property.kind),
+        _ => None,
+    };
+    assert_eq!(Some(PropertyKind::Scalar(ValueKind::Int32)), kinds(b"property int x\n"));
+    assert_eq!(Some(PropertyKind::Scalar(ValueKind::Int8)), kinds(b"property int8 x\n"));
+    assert_eq!(Some(PropertyKind::Scalar(ValueKind::Float64)), kinds(b"property float64 x\n"));
+    assert_eq!(Some(PropertyKind::List(ValueKind::UInt8, ValueKind::UInt32)),
+               kinds(b"property list uchar uint x\n"));
+    assert_eq!(None, kinds(b"property charx x\n"));
+    assert_eq!(None, kinds(b"property list int8x int x\n"));
+    assert!(matches!(header_error(b"ply\nformat ascii 1.0\nelement face 1\n\
+                                    property list uchar int16x vertex_index\nend_header\n"),
+                     PlyError::UnknownType { line: 4, ref name } if name == "int16x"));
+}
+
+#[cfg(test)]
+fn header_error(input: &[u8]) -> PlyError {
+    match header(input, &ReadOptions::default()) {
+        Ok((_, header)) => panic!("expected an error, got {:?}", header),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn obj_info_test() {
+    let input = b"ply\nformat ascii 1.0\nobj_info scanner 3\ncomment first\nobj_info\n\
+                  element vertex 0\nproperty float x\nend_header\n";
+    let (_, header) = header(input, &ReadOptions::default()).unwrap();
+    assert_eq!(vec![Comment {
+                        line: 4,
+                        text: "first".into(),
+                    }],
+               header.comments);
+    assert_eq!(vec![Comment {
+                        line: 3,
+                        text: "scanner 3".into(),
+                    },
+                    Comment {
+                        line: 5,
+                        text: "".into(),
+                    }],
+               header.obj_infos);
+}
+
+#[test]
+fn identifier_test() {
+    let lenient = ReadOptions::default();
+    let strict = ReadOptions { strict: true, ..Default::default() };
+    let input = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float f_rest_12\n\
+                  property uchar red.channel\nproperty float texture-u1\nend_header\n";
+    let names = header(input, &lenient).unwrap().1.elements[0]
+        .properties
+        .iter()
+        .map(|p| p.name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["f_rest_12", "red.channel", "texture-u1"], names);
+    assert!(matches!(header(input, &strict), Err(PlyError::BadHeaderLine { line: 5 })));
+
+    let input = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float 1x\nend_header\n";
+    assert!(header(input, &lenient).is_ok());
+    assert!(matches!(header(input, &strict), Err(PlyError::BadHeaderLine { line: 4 })));
+
+    // A declaration cannot continue on the next line.
+    let input = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float\nx\nend_header\n";
+    assert!(matches!(header(input, &lenient), Err(PlyError::BadHeaderLine { line: 4 })));
+}
+
+#[test]
+fn version_test() {
+    let input = b"ply\nformat binary_big_endian 1.1\nelement vertex 1\nproperty float x\n\
+                  end_header\n";
+    let options = ReadOptions { allow_unknown_versions: true, ..Default::default() };
+    let (_, header) = header(input, &options).unwrap();
+    assert_eq!(Version { major: 1, minor: 1 }, header.format.version);
+    assert_eq!("line 2: unsupported format version 1.1", header_error(input).to_string());
+}
+
+#[test]
+fn header_error_test() {
+    assert!(matches!(header_error(b"plx\nformat ascii 1.0\n"), PlyError::BadMagic));
+    assert!(matches!(header_error(b"ply\nformat ascii 2.0\nelement vertex 1\n"),
+                     PlyError::UnsupportedVersion {
+                         line: 2,
+                         version: Version { major: 2, minor: 0 },
+                     }));
+    assert!(matches!(header_error(b"ply\nformat text 1.0\nelement vertex 1\n"),
+                     PlyError::BadFormat { line: 2 }));
+    assert!(matches!(header_error(b"ply\nformat ascii 1.0\nelement vertex 1\n\
+                                    property float x\nproperty flaot y\nend_header\n"),
+                     PlyError::UnknownType { line: 5, ref name } if name == "flaot"));
+    assert!(matches!(header_error(b"ply\nformat ascii 1.0\nelement face 1\n\
+                                    property list uchar in vertex_index\nend_header\n"),
+                     PlyError::UnknownType { line: 4, ref name } if name == "in"));
+    assert!(matches!(header_error(b"ply\nformat ascii 1.0\nproperty float x\nend_header\n"),
+                     PlyError::BadHeaderLine { line: 3 }));
+    assert!(matches!(header_error(b"ply\ncomment x\nelement vertex 1\nproperty float x\n\
+                                    end_header\n"),
+                     PlyError::BadFormat { line: 3 }));
+    assert!(matches!(header_error(b"ply\nformat ascii 1.0\nformat ascii 1.0\n\
+                                    element vertex 1\nproperty float x\nend_header\n"),
+                     PlyError::BadFormat { line: 3 }));
+    assert!(matches!(header_error(b"ply\nformat ascii 1.0\nelement vertex 1\n\
+                                    property float x\n"),
+                     PlyError::TruncatedHeader { line: 5 }));
+    assert!(matches!(header_error(b"ply\nformat ascii 1.0\ncommentary x\nelement vertex 1\n\
+                                    property float x\nend_header\n"),
+                     PlyError::BadHeaderLine { line: 3 }));
+    assert!(matches!(header_error(b"ply\nformat ascii 1.0\nobj_infox y\nelement vertex 1\n\
+                                    property float x\nend_header\n"),
+                     PlyError::BadHeaderLine { line: 3 }));
+}
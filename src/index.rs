@@ -0,0 +1,284 @@
+use body::Record;
+use error::PlyError;
+use header::FormatKind;
+use options::ReadOptions;
+use reader::{read_records_at, Reader};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"PLYINDEX";
+const VERSION: u64 = 1;
+
+/// Where the records of one element start in the file.
+#[derive(Debug, Clone, PartialEq)]
+enum ElementIndex {
+    /// Binary records without lists: record 'i' starts at 'start + i * stride'.
+    Fixed { start: usize, stride: usize },
+    /// Any other records: 'offsets[k]' is the start of record 'k * interval'.
+    Sampled { interval: usize, offsets: Vec<usize> },
+}
+
+/// Byte offsets of the records of every element of a PLY file, for reading any range of records
+/// with 'Index::read_range' without decoding the records before it.
+///
+/// Building an index reads the whole file once. It can be kept in a sidecar file next to the PLY
+/// file, see 'Index::sidecar_path'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Index {
+    // The length of the indexed file, to tell when an index does not belong to a file.
+    len: usize,
+    elements: Vec<ElementIndex>,
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: usize) -> Result<(), PlyError> {
+    writer.write_all(&(value as u64).to_le_bytes())?;
+    Ok(())
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<usize, PlyError> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    let value = u64::from_le_bytes(bytes);
+    if value > usize::MAX as u64 {
+        return Err(PlyError::BadIndex);
+    }
+    Ok(value as usize)
+}
+
+impl Index {
+    /// Indexes the PLY file in 'reader'. Elements that are not binary records of a fixed size get
+    /// the offset of every 'interval'th record, a larger 'interval' makes the index smaller but
+    /// 'read_range' skip more records on average.
+    pub fn build<R: BufRead>(reader: R, interval: usize) -> Result<Index, PlyError> {
+        Self::build_with_options(reader, interval, &ReadOptions::default())
+    }
+
+    pub fn build_with_options<R: BufRead>(reader: R,
+                                          interval: usize,
+                                          options: &ReadOptions)
+                                          -> Result<Index, PlyError> {
+        let interval = interval.max(1);
+        let mut reader = Reader::with_options(reader, options)?;
+        let binary = reader.header().format.kind != FormatKind::Ascii;
+        let mut elements = Vec::new();
+        while let Some(mut records) = reader.next_element()? {
+            let count = records.element().count as usize;
+            match records.element().record_size() {
+                Some(stride) if binary => {
+                    elements.push(ElementIndex::Fixed {
+                        start: records.offset(),
+                        stride,
+                    })
+                }
+                _ => {
+                    let mut offsets = Vec::with_capacity(count.div_ceil(interval));
+                    for _ in 0..count.div_ceil(interval) {
+                        offsets.push(records.offset());
+                        records.skip_records(interval)?;
+                    }
+                    elements.push(ElementIndex::Sampled { interval, offsets });
+                }
+            }
+        }
+        Ok(Index {
+            len: reader.offset(),
+            elements,
+        })
+    }
+
+    /// Where the sidecar index of the PLY file at 'path' is kept: next to it, with '.idx' appended.
+    pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut path = path.as_ref().as_os_str().to_owned();
+        path.push(".idx");
+        PathBuf::from(path)
+    }
+
+    /// Writes the index, e.g. to the file at 'sidecar_path'.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), PlyError> {
+        writer.write_all(MAGIC)?;
+        write_u64(&mut writer, VERSION as usize)?;
+        write_u64(&mut writer, self.len)?;
+        write_u64(&mut writer, self.elements.len())?;
+        for element in &self.elements {
+            match *element {
+                ElementIndex::Fixed { start, stride } => {
+                    writer.write_all(&[0])?;
+                    write_u64(&mut writer, start)?;
+                    write_u64(&mut writer, stride)?;
+                }
+                ElementIndex::Sampled { interval, ref offsets } => {
+                    writer.write_all(&[1])?;
+                    write_u64(&mut writer, interval)?;
+                    write_u64(&mut writer, offsets.len())?;
+                    for &offset in offsets {
+                        write_u64(&mut writer, offset)?;
+                    }
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads an index written by 'write_to'.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Index, PlyError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u64(&mut reader)? != VERSION as usize {
+            return Err(PlyError::BadIndex);
+        }
+        let len = read_u64(&mut reader)?;
+        let count = read_u64(&mut reader)?;
+        let mut elements = Vec::new();
+        for _ in 0..count {
+            let mut tag = [0];
+            reader.read_exact(&mut tag)?;
+            elements.push(match tag[0] {
+                0 => {
+                    ElementIndex::Fixed {
+                        start: read_u64(&mut reader)?,
+                        stride: read_u64(&mut reader)?,
+                    }
+                }
+                1 => {
+                    let interval = read_u64(&mut reader)?;
+                    let samples = read_u64(&mut reader)?;
+                    let offsets = (0..samples)
+                        .map(|_| read_u64(&mut reader))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    ElementIndex::Sampled { interval, offsets }
+                }
+                _ => return Err(PlyError::BadIndex),
+            });
+        }
+        Ok(Index { len, elements })
+    }
+
+    /// Decodes the records 'range' of the element 'name' from 'file', the file the index was
+    /// built for. The range is cut off at the number of records of the element.
+    pub fn read_range<R: Read + Seek>(&self,
+                                      file: R,
+                                      name: &str,
+                                      range: Range<usize>)
+                                      -> Result<Vec<Record>, PlyError> {
+        self.read_range_with_options(file, name, range, &ReadOptions::default())
+    }
+
+    /// Like 'read_range', 'options' must be those the index was built with.
+    pub fn read_range_with_options<R: Read + Seek>(&self,
+                                                   mut file: R,
+                                                   name: &str,
+                                                   range: Range<usize>,
+                                                   options: &ReadOptions)
+                                                   -> Result<Vec<Record>, PlyError> {
+        if file.seek(SeekFrom::End(0))? != self.len as u64 {
+            return Err(PlyError::BadIndex);
+        }
+        file.seek(SeekFrom::Start(0))?;
+        let header = Reader::with_options(BufReader::new(&mut file), options)?.into_header();
+        if header.elements.len() != self.elements.len() {
+            return Err(PlyError::BadIndex);
+        }
+        let index = header.elements
+            .iter()
+            .position(|element| element.name == name)
+            .ok_or_else(|| PlyError::NoSuchElement { name: name.to_string() })?;
+        let count = header.elements[index].count as usize;
+        let (start, end) = (range.start.min(count), range.end.min(count));
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        // The index comes from a file as well, so do not trust its offsets.
+        let (offset, skip) = match self.elements[index] {
+            ElementIndex::Fixed { start: first, stride } => {
+                let offset = start.checked_mul(stride).and_then(|offset| offset.checked_add(first));
+                (offset.ok_or(PlyError::BadIndex)?, 0)
+            }
+            ElementIndex::Sampled { interval: 0, .. } => return Err(PlyError::BadIndex),
+            ElementIndex::Sampled { interval, ref offsets } => {
+                let sample = start / interval;
+                let offset = *offsets.get(sample).ok_or(PlyError::BadIndex)?;
+                (offset, start - sample * interval)
+            }
+        };
+        if offset >= self.len {
+            return Err(PlyError::BadIndex);
+        }
+        file.seek(SeekFrom::Start(offset as u64))?;
+        read_records_at(BufReader::new(file), header, offset, index, skip, end - start)
+    }
+}
+
+#[test]
+fn ascii_index_test() {
+    use body::{PropertyValue, Value};
+    use std::fs::File;
+
+    let path = "testdata/beethoven.ply";
+    let index = Index::build(BufReader::new(File::open(path).unwrap()), 100).unwrap();
+    let faces = index.read_range(File::open(path).unwrap(), "face", 5028..6000).unwrap();
+    assert_eq!(2, faces.len());
+    assert_eq!(vec![PropertyValue::List(vec![Value::Int32(850),
+                                             Value::Int32(2520),
+                                             Value::Int32(2515)])],
+               faces[1]);
+    let vertices = index.read_range(File::open(path).unwrap(), "vertex", 0..1).unwrap();
+    assert_eq!(PropertyValue::Scalar(Value::Float32(-0.093362)), vertices[0][0]);
+    assert!(matches!(index.read_range(File::open(path).unwrap(), "edge", 0..1),
+                     Err(PlyError::NoSuchElement { .. })));
+}
+
+#[test]
+fn binary_index_test() {
+    use body::{PropertyValue, Value};
+    use std::io::Cursor;
+
+    let mut ply = ::read(::std::fs::File::open("testdata/beethoven.ply").unwrap()).unwrap();
+    ply.header.format.kind = FormatKind::BigEndian;
+    let mut file = Vec::new();
+    ::write(&mut file, &ply).unwrap();
+
+    let index = Index::build(&file[..], 7).unwrap();
+    let mut sidecar = Vec::new();
+    index.write_to(&mut sidecar).unwrap();
+    let index = Index::read_from(&sidecar[..]).unwrap();
+    assert!(matches!(index.elements[0], ElementIndex::Fixed { stride: 12, .. }));
+    assert!(matches!(index.elements[1], ElementIndex::Sampled { interval: 7, .. }));
+
+    let vertices = index.read_range(Cursor::new(&file), "vertex", 2520..2521).unwrap();
+    assert_eq!(ply.payload["vertex"][2520], vertices[0]);
+    let faces = index.read_range(Cursor::new(&file), "face", 5029..5030).unwrap();
+    assert_eq!(vec![PropertyValue::List(vec![Value::Int32(850),
+                                             Value::Int32(2520),
+                                             Value::Int32(2515)])],
+               faces[0]);
+    // An index for another file is rejected.
+    assert!(matches!(index.read_range(Cursor::new(&file[1..]), "face", 0..1),
+                     Err(PlyError::BadIndex)));
+}
+
+#[test]
+fn untrusted_index_test() {
+    use std::io::Cursor;
+
+    let input = b"ply\nformat binary_little_endian 1.1\nelement vertex 2\nproperty uchar x\n\
+                  end_header\n\x01\x02";
+    let options = ReadOptions { allow_unknown_versions: true, ..Default::default() };
+    assert!(Index::build(&input[..], 1).is_err());
+    let index = Index::build_with_options(&input[..], 1, &options).unwrap();
+    let records = index.read_range_with_options(Cursor::new(&input[..]), "vertex", 1..2, &options)
+        .unwrap();
+    assert_eq!(vec![vec![::PropertyValue::Scalar(::Value::UInt8(2))]], records);
+
+    for element in [ElementIndex::Fixed { start: 1, stride: usize::MAX },
+                    ElementIndex::Fixed { start: 1000, stride: 1 },
+                    ElementIndex::Sampled { interval: 0, offsets: vec![70] }] {
+        let index = Index { elements: vec![element], ..index.clone() };
+        assert!(matches!(index.read_range_with_options(Cursor::new(&input[..]),
+                                                       "vertex",
+                                                       1..2,
+                                                       &options),
+                         Err(PlyError::BadIndex)));
+    }
+}
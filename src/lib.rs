@@ -0,0 +1,184 @@
+//! A parser for PLY (polygon file format) files.
+//!
+//! 'parse' decodes a file that is already in memory, 'read' decodes everything that can be read
+//! from a 'std::io::Read' and 'Reader' streams the records of files too large for memory.
+//! 'write' encodes a 'Ply' again in any of the three formats.
+//!
+//! To avoid matching on 'Value' for every property, implement 'PropertyAccess' for your own record
+//! type and load an element with 'ElementReader::read_into'. 'ElementReader::read_columns' stores
+//! an element as one contiguous 'Column' per property instead. With the 'serde' feature, records
+//! also deserialize into any 'serde::Deserialize' type with 'ElementReader::deserialize_into' or
+//! 'from_record', and 'to_records' serializes 'serde::Serialize' items into records of an element.
+//! With the 'mmap' feature, 'MappedPly' reads the records of binary files in place. An 'Index'
+//! of record offsets lets 'Index::read_range' read any range of records of a file, and
+//! 'Reader::read_projected' decodes only the elements and properties named in a 'Projection'.
+//! With the 'rayon' feature, 'parse_parallel' decodes large bodies across threads.
+//!
+//! Files compressed with gzip, zstd or xz are decompressed transparently by 'parse' and 'read'
+//! if the 'gzip', 'zstd' or 'xz' feature is enabled, and 'write_compressed' writes them.
+
+#[macro_use]
+extern crate nom;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "xz")]
+extern crate xz2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+
+mod access;
+mod body;
+mod columns;
+mod compression;
+#[cfg(feature = "serde")]
+mod de;
+mod error;
+mod header;
+mod index;
+#[cfg(feature = "mmap")]
+mod mapped;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod projection;
+mod reader;
+#[cfg(feature = "serde")]
+mod ser;
+mod validate;
+mod value;
+mod writer;
+
+pub use access::PropertyAccess;
+#[doc(hidden)]
+pub use access::{FromProperty, FromValue};
+pub use body::{Ply, PropertyValue, Record, Value};
+pub use columns::{Column, Columns, PropertyColumn, Scalar};
+pub use compression::{decompress, write_compressed, Compression};
+#[cfg(feature = "serde")]
+pub use de::from_record;
+pub use error::{ConversionError, PlyError};
+pub use header::{Comment, Element, Format, FormatKind, Header, Property, PropertyKind, ValueKind,
+                 Version};
+pub use index::Index;
+#[cfg(feature = "mmap")]
+pub use mapped::{MappedPly, StridedColumn, StridedElement};
+pub use options::ReadOptions;
+pub use projection::Projection;
+pub use reader::{ElementReader, Reader};
+#[cfg(feature = "serde")]
+pub use ser::to_records;
+pub use validate::{Diagnostic, Issue};
+pub use writer::{write, write_header};
+
+use std::collections::HashMap;
+use std::io::Read;
+
+/// The contents of the compressed file 'input'.
+fn decompressed(input: &[u8]) -> Result<Vec<u8>, PlyError> {
+    let mut output = Vec::new();
+    decompress(input)?.read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// Parses a complete PLY file, header and body, from 'input'. Compressed files are decompressed
+/// first, see 'decompress'.
+pub fn parse(input: &[u8]) -> Result<Ply, PlyError> {
+    parse_with_options(input, &ReadOptions::default())
+}
+
+pub fn parse_with_options(input: &[u8], options: &ReadOptions) -> Result<Ply, PlyError> {
+    if Compression::detect(input).is_some() {
+        return parse_with_options(&decompressed(input)?, options);
+    }
+    let (rest, header) = header::header(input, options)?;
+    let payload = body::body(rest, input.len() - rest.len(), &header)?;
+    Ok(Ply { header, payload })
+}
+
+/// Like 'parse', but decodes binary elements without list properties and ASCII bodies with one
+/// record per line across threads.
+#[cfg(feature = "rayon")]
+pub fn parse_parallel(input: &[u8]) -> Result<Ply, PlyError> {
+    parse_parallel_with_options(input, &ReadOptions::default())
+}
+
+#[cfg(feature = "rayon")]
+pub fn parse_parallel_with_options(input: &[u8], options: &ReadOptions) -> Result<Ply, PlyError> {
+    if Compression::detect(input).is_some() {
+        return parse_parallel_with_options(&decompressed(input)?, options);
+    }
+    let (rest, header) = header::header(input, options)?;
+    let payload = parallel::body(rest, input.len() - rest.len(), &header)?;
+    Ok(Ply { header, payload })
+}
+
+/// Reads the PLY file in 'reader' and decodes all of its records, decompressing it first if
+/// needed. Use 'Reader' directly to process one record at a time instead.
+pub fn read<R: Read>(reader: R) -> Result<Ply, PlyError> {
+    read_with_options(reader, &ReadOptions::default())
+}
+
+pub fn read_with_options<R: Read>(reader: R, options: &ReadOptions) -> Result<Ply, PlyError> {
+    let mut reader = Reader::with_options(decompress(reader)?, options)?;
+    let mut payload = HashMap::new();
+    while let Some(element) = reader.next_element()? {
+        let name = element.element().name.clone();
+        payload.insert(name, element.collect::<Result<Vec<_>, _>>()?);
+    }
+    Ok(Ply {
+        header: reader.into_header(),
+        payload,
+    })
+}
+
+#[test]
+fn beethoven_test() {
+    let ply = read(std::fs::File::open("testdata/beethoven.ply").unwrap()).unwrap();
+    assert_eq!(2521, ply.payload["vertex"].len());
+    assert_eq!(vec![PropertyValue::Scalar(Value::Float32(-0.093362)),
+                    PropertyValue::Scalar(Value::Float32(-0.478222)),
+                    PropertyValue::Scalar(Value::Float32(0.606843))],
+               ply.payload["vertex"][0]);
+    assert_eq!(5030, ply.payload["face"].len());
+    assert_eq!(vec![PropertyValue::List(vec![Value::Int32(850),
+                                             Value::Int32(2520),
+                                             Value::Int32(2515)])],
+               ply.payload["face"][5029]);
+}
+
+#[test]
+fn binary_body_test() {
+    // The first body byte is a space, which must not be swallowed by the header.
+    let ply = parse(b"ply\nformat binary_little_endian 1.0\nelement vertex 2\nproperty uchar a\n\
+                      property int16 b\nend_header\n\x20\xfe\xff\x07\x01\x00")
+        .unwrap();
+    assert_eq!(vec![vec![PropertyValue::Scalar(Value::UInt8(0x20)),
+                         PropertyValue::Scalar(Value::Int16(-2))],
+                    vec![PropertyValue::Scalar(Value::UInt8(7)),
+                         PropertyValue::Scalar(Value::Int16(1))]],
+               ply.payload["vertex"]);
+}
+
+#[test]
+fn body_error_test() {
+    // The header is 64 bytes long.
+    let header = b"ply\nformat ascii 1.0\nelement vertex 2\nproperty int x\nend_header\n";
+    let parse_body = |body: &[u8]| parse(&[&header[..], body].concat());
+    assert!(matches!(parse_body(b"1\n"),
+                     Err(PlyError::TruncatedBody { offset: 66, element: 0 })));
+    assert!(matches!(parse_body(b"1\n2\n3\n"),
+                     Err(PlyError::CountMismatch { offset: 68, element: 0 })));
+    assert!(matches!(parse_body(b"1\n2.5\n"),
+                     Err(PlyError::InvalidNumber { offset: 66, element: 0 })));
+    assert!(parse_body(b"1\n2\n\n").is_ok());
+}
@@ -0,0 +1,268 @@
+use body::binary_record_len;
+use columns::Scalar;
+use error::PlyError;
+use header::{header, Element, FormatKind, Header, PropertyKind};
+use memmap2::Mmap;
+use options::ReadOptions;
+use std::fs::File;
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// Where the records of an element are in the body and how far apart.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    start: usize,
+    stride: usize,
+}
+
+/// A binary PLY file mapped into memory. Elements with only scalar properties have records of
+/// a fixed size and are accessed in place through 'MappedPly::element', without decoding the
+/// rest of the file.
+pub struct MappedPly {
+    map: Mmap,
+    header: Header,
+    // Position of the body in 'map'.
+    body: usize,
+    // One per element, 'None' if its records do not have a fixed size.
+    layouts: Vec<Option<Layout>>,
+}
+
+/// Skips the records of 'element', which has list properties, starting at 'position' in 'body'.
+/// Returns the position after the last record or the position at which the body is invalid or
+/// ends.
+fn skip_records(body: &[u8],
+                mut position: usize,
+                element: &Element,
+                big_endian: bool)
+                -> Result<usize, (usize, bool)> {
+    for _ in 0..element.count {
+        match binary_record_len(&body[position..], element, big_endian) {
+            Ok(Some(len)) => position += len,
+            Ok(None) => return Err((body.len(), true)),
+            Err(at) => return Err((position + at, false)),
+        }
+    }
+    Ok(position)
+}
+
+impl MappedPly {
+    /// Maps the file at 'path' and parses its header.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, PlyError> {
+        Self::open_with_options(path, &ReadOptions::default())
+    }
+
+    pub fn open_with_options<P: AsRef<Path>>(path: P,
+                                             options: &ReadOptions)
+                                             -> Result<Self, PlyError> {
+        let file = File::open(path)?;
+        // The map is only ever read. Like any memory map it relies on the file not being
+        // truncated or modified while it is mapped.
+        let map = unsafe { Mmap::map(&file)? };
+        let (body, header) = {
+            let (body, header) = header(&map, options)?;
+            (map.len() - body.len(), header)
+        };
+        let big_endian = match header.format.kind {
+            FormatKind::Ascii => {
+                let layouts = header.elements.iter().map(|_| None).collect();
+                return Ok(MappedPly { map, header, body, layouts });
+            }
+            FormatKind::BigEndian => true,
+            FormatKind::LittleEndian => false,
+        };
+
+        let data = &map[body..];
+        let mut layouts = Vec::with_capacity(header.elements.len());
+        let mut position = 0;
+        for (index, element) in header.elements.iter().enumerate() {
+            let end = match element.record_size() {
+                Some(stride) => {
+                    layouts.push(Some(Layout { start: position, stride }));
+                    (element.count as usize)
+                        .checked_mul(stride)
+                        .and_then(|size| size.checked_add(position))
+                        .filter(|&end| end <= data.len())
+                        .ok_or(PlyError::TruncatedBody {
+                            offset: map.len(),
+                            element: index,
+                        })?
+                }
+                None => {
+                    layouts.push(None);
+                    skip_records(data, position, element, big_endian).map_err(|(at, truncated)| {
+                            let offset = body + at;
+                            if truncated {
+                                PlyError::TruncatedBody { offset, element: index }
+                            } else {
+                                PlyError::InvalidNumber { offset, element: index }
+                            }
+                        })?
+                }
+            };
+            position = end;
+        }
+        Ok(MappedPly { map, header, body, layouts })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// A view of the records of the element 'name'. Fails with 'PlyError::NotFixedSize' if the
+    /// element has list properties or the file is ASCII.
+    pub fn element(&self, name: &str) -> Option<Result<StridedElement<'_>, PlyError>> {
+        let index = self.header.elements.iter().position(|element| element.name == name)?;
+        let element = &self.header.elements[index];
+        Some(match self.layouts[index] {
+            Some(layout) => {
+                let start = self.body + layout.start;
+                let end = start + layout.stride * element.count as usize;
+                Ok(StridedElement {
+                    data: &self.map[start..end],
+                    element,
+                    stride: layout.stride,
+                    big_endian: self.header.format.kind == FormatKind::BigEndian,
+                })
+            }
+            None => Err(PlyError::NotFixedSize { element: index }),
+        })
+    }
+}
+
+/// The records of an element in a 'MappedPly'. Values are read straight from the mapped file
+/// and byte swapped if the file's endianness differs from the host's.
+pub struct StridedElement<'a> {
+    data: &'a [u8],
+    element: &'a Element,
+    stride: usize,
+    big_endian: bool,
+}
+
+impl<'a> StridedElement<'a> {
+    pub fn element(&self) -> &'a Element {
+        self.element
+    }
+
+    /// The number of records.
+    pub fn len(&self) -> usize {
+        self.element.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The encoded bytes of 'record'.
+    pub fn record(&self, record: usize) -> Option<&'a [u8]> {
+        let start = record.checked_mul(self.stride)?;
+        self.data.get(start..start + self.stride)
+    }
+
+    /// The property 'name' of all records, if it exists and has type 'T'. Look up a column once
+    /// to access many records, rather than calling 'get' for each.
+    pub fn column<T: Scalar>(&self, name: &str) -> Option<StridedColumn<'a, T>> {
+        let mut offset = 0;
+        for property in &self.element.properties {
+            match property.kind {
+                PropertyKind::Scalar(kind) if property.name == name => {
+                    if kind != T::KIND {
+                        return None;
+                    }
+                    return Some(StridedColumn {
+                        data: self.data,
+                        offset,
+                        stride: self.stride,
+                        big_endian: self.big_endian,
+                        marker: PhantomData,
+                    });
+                }
+                PropertyKind::Scalar(kind) => offset += kind.size(),
+                PropertyKind::List(..) => unreachable!("elements with lists have no view"),
+            }
+        }
+        None
+    }
+
+    /// The property 'name' of 'record', if both exist and the property has type 'T'.
+    pub fn get<T: Scalar>(&self, record: usize, name: &str) -> Option<T> {
+        self.column(name)?.get(record)
+    }
+}
+
+/// One scalar property of all records of a 'StridedElement'.
+pub struct StridedColumn<'a, T> {
+    data: &'a [u8],
+    // Position of the property in a record.
+    offset: usize,
+    stride: usize,
+    big_endian: bool,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: Scalar> StridedColumn<'a, T> {
+    pub fn len(&self) -> usize {
+        self.data.len() / self.stride
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn get(&self, record: usize) -> Option<T> {
+        let start = record.checked_mul(self.stride)? + self.offset;
+        let bytes = self.data.get(start..start + T::KIND.size())?;
+        Some(T::from_bytes(bytes, self.big_endian))
+    }
+
+    /// The values of all records in order.
+    pub fn iter(&self) -> impl Iterator<Item = T> + 'a
+        where T: 'a
+    {
+        let (offset, size, big_endian) = (self.offset, T::KIND.size(), self.big_endian);
+        self.data
+            .chunks(self.stride)
+            .map(move |record| T::from_bytes(&record[offset..offset + size], big_endian))
+    }
+}
+
+#[cfg(test)]
+fn mapped(name: &str, contents: &[u8]) -> MappedPly {
+    let path = ::std::env::temp_dir().join(format!("ply-{}-{}.ply", name, ::std::process::id()));
+    ::std::fs::write(&path, contents).unwrap();
+    let mapped = MappedPly::open(&path).unwrap();
+    ::std::fs::remove_file(&path).unwrap();
+    mapped
+}
+
+#[test]
+fn strided_element_test() {
+    let ply = mapped("strided",
+                     b"ply\nformat binary_big_endian 1.0\nelement face 2\n\
+                       property list uchar ushort i\nelement vertex 2\nproperty uchar flags\n\
+                       property float x\nend_header\n\
+                       \x01\x00\x07\x00\
+                       \x07\x3f\x80\x00\x00\x00\x40\x00\x00\x00");
+    assert!(matches!(ply.element("face"), Some(Err(PlyError::NotFixedSize { element: 0 }))));
+    assert!(ply.element("edge").is_none());
+    let vertices = ply.element("vertex").unwrap().unwrap();
+    assert_eq!(2, vertices.len());
+    assert_eq!(Some(1.0f32), vertices.get(0, "x"));
+    assert_eq!(Some(7u8), vertices.get(0, "flags"));
+    assert_eq!(None, vertices.get::<f64>(0, "x"));
+    assert_eq!(None, vertices.get::<f32>(2, "x"));
+    let x = vertices.column::<f32>("x").unwrap();
+    assert_eq!(vec![1.0, 2.0], x.iter().collect::<Vec<_>>());
+    assert_eq!(Some(&[0u8, 0x40, 0, 0, 0][..]), vertices.record(1));
+}
+
+#[test]
+fn truncated_mapped_test() {
+    let path = ::std::env::temp_dir().join(format!("ply-truncated-{}.ply", ::std::process::id()));
+    ::std::fs::write(&path,
+                     b"ply\nformat binary_little_endian 1.0\nelement vertex 2\nproperty int x\n\
+                       end_header\n\x01\x00\x00\x00")
+        .unwrap();
+    let result = MappedPly::open(&path);
+    ::std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(PlyError::TruncatedBody { offset: 83, element: 0 })));
+}
@@ -0,0 +1,12 @@
+/// Options controlling how PLY files are parsed. The default is lenient.
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// Only accept element and property names made up of ASCII letters, digits and '_' that do
+    /// not start with a digit, and reject headers for which 'Header::validate' reports errors.
+    /// Otherwise any token without whitespace is a valid name and only syntax and unique element
+    /// names are checked.
+    pub strict: bool,
+    /// Accept files whose 'format' line names a version other than 1.0 and read them as 1.0.
+    /// Without it such files are rejected with 'PlyError::UnsupportedVersion'.
+    pub allow_unknown_versions: bool,
+}
@@ -0,0 +1,187 @@
+use body::{check_trailing, decode_element, decode_record, Record};
+use error::PlyError;
+use header::{FormatKind, Header};
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+// The fewest records a thread decodes at a time, so that small elements are not split up at all.
+const MIN_RECORDS: usize = 4096;
+// The fewest bytes of an ASCII body a thread decodes at a time.
+const MIN_CHUNK: usize = 1 << 16;
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(|c| b" \t\r\n".contains(c))
+}
+
+/// The lines of 'chunk' that are not blank.
+fn lines(chunk: &[u8]) -> impl Iterator<Item = &[u8]> {
+    chunk.split(|&c| c == b'\n').filter(|line| !is_blank(line))
+}
+
+/// Decodes an ASCII body in which every record is on a line of its own across threads. The body
+/// is split into chunks at line ends, the lines of each chunk are counted to tell which element
+/// they belong to and then decoded. Returns 'None' if any line is not exactly one record, or the
+/// number of lines does not match the element counts.
+fn ascii_lines(input: &[u8], header: &Header) -> Option<HashMap<String, Vec<Record>>> {
+    let size = (input.len() / (4 * rayon::current_num_threads())).max(MIN_CHUNK);
+    let mut chunks = Vec::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let end = match rest.iter().skip(size).position(|&c| c == b'\n') {
+            Some(newline) => size + newline + 1,
+            None => rest.len(),
+        };
+        let (chunk, remaining) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = remaining;
+    }
+
+    // The index of the first line of each chunk, and of the first line after each element.
+    let mut first = chunks.par_iter().map(|chunk| lines(chunk).count()).collect::<Vec<_>>();
+    let mut total = 0;
+    for count in &mut first {
+        total += *count;
+        *count = total - *count;
+    }
+    // Counts too large to add up cannot match the lines anyway, leave them to 'body::body'.
+    let mut ends = Vec::with_capacity(header.elements.len());
+    let mut end = 0usize;
+    for element in &header.elements {
+        end = end.checked_add(element.count as usize)?;
+        ends.push(end);
+    }
+    if ends.last().cloned().unwrap_or(0) != total {
+        return None;
+    }
+
+    let decoded = chunks.par_iter()
+        .zip(first)
+        .map(|(chunk, first)| {
+            let mut records = Vec::new();
+            let mut index = ends.iter().position(|&end| end > first).unwrap_or(0);
+            for (line, record) in lines(chunk).zip(first..) {
+                while ends[index] <= record {
+                    index += 1;
+                }
+                match decode_record(line, 0, header, index) {
+                    Ok(Some((rest, record))) if is_blank(rest) => records.push(record),
+                    _ => return None,
+                }
+            }
+            Some(records)
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    // Stitch the records of all chunks back together and hand them out by element.
+    let mut records = decoded.into_iter().flatten();
+    Some(header.elements
+        .iter()
+        .map(|element| {
+            (element.name.clone(), records.by_ref().take(element.count as usize).collect())
+        })
+        .collect())
+}
+
+/// Decodes the records of the element at 'index', which have a binary size of 'size' each, across
+/// threads. Returns the input after the last record.
+fn decode_fixed<'a>(input: &'a [u8],
+                    offset: usize,
+                    header: &Header,
+                    index: usize,
+                    size: usize)
+                    -> Result<(&'a [u8], Vec<Record>), PlyError> {
+    let count = header.elements[index].count as usize;
+    let len = count.saturating_mul(size);
+    if len > input.len() {
+        // Report the first record that is cut off, like decoding one after the other does.
+        return Err(PlyError::TruncatedBody {
+            offset: offset + input.len() / size * size,
+            element: index,
+        });
+    }
+    let (data, rest) = input.split_at(len);
+    let records = data.par_chunks(size)
+        .with_min_len(MIN_RECORDS)
+        .enumerate()
+        .map(|(record, bytes)| {
+            let offset = offset + record * size;
+            match decode_record(bytes, offset, header, index)? {
+                Some((_, record)) => Ok(record),
+                None => Err(PlyError::TruncatedBody { offset, element: index }),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((rest, records))
+}
+
+/// Like 'body::body', but decodes the records of binary elements without list properties and
+/// ASCII bodies with one record per line across threads. The result is the same. Other ASCII
+/// bodies, including invalid ones, are decoded one record after the other.
+pub fn body(input: &[u8],
+            offset: usize,
+            header: &Header)
+            -> Result<HashMap<String, Vec<Record>>, PlyError> {
+    if header.format.kind == FormatKind::Ascii {
+        if let Some(payload) = ascii_lines(input, header) {
+            return Ok(payload);
+        }
+        return ::body::body(input, offset, header);
+    }
+    let offset_of = |remaining: &[u8]| offset + input.len() - remaining.len();
+    let mut rest = input;
+    let mut payload = HashMap::new();
+    for (index, element) in header.elements.iter().enumerate() {
+        let (remaining, records) = match element.record_size().filter(|&size| size > 0) {
+            Some(size) => decode_fixed(rest, offset_of(rest), header, index, size)?,
+            None => decode_element(rest, offset_of(rest), header, index)?,
+        };
+        rest = remaining;
+        payload.insert(element.name.clone(), records);
+    }
+    check_trailing(rest, offset_of(rest), header)?;
+    Ok(payload)
+}
+
+#[test]
+fn parallel_binary_test() {
+    use body::{PropertyValue, Value};
+    use options::ReadOptions;
+
+    let mut input = b"ply\nformat binary_big_endian 1.0\nelement vertex 10000\n\
+                      property ushort x\nproperty char y\nend_header\n"
+        .to_vec();
+    let start = input.len();
+    input.extend((0..10000u16).flat_map(|i| vec![(i >> 8) as u8, i as u8, i as u8]));
+    let header = ::header::header(&input, &ReadOptions::default()).unwrap().1;
+    let payload = body(&input[start..], start, &header).unwrap();
+    assert_eq!(::body::body(&input[start..], start, &header).unwrap(), payload);
+    assert_eq!(vec![PropertyValue::Scalar(Value::UInt16(9999)),
+                    PropertyValue::Scalar(Value::Int8(9999u16 as u8 as i8))],
+               payload["vertex"][9999]);
+    let truncated = body(&input[start..start + 29998], start, &header);
+    assert!(matches!(truncated, Err(PlyError::TruncatedBody { offset, element: 0 })
+                                if offset == start + 29997));
+}
+
+#[test]
+fn parallel_ascii_test() {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut input = Vec::new();
+    File::open("testdata/beethoven.ply").unwrap().read_to_end(&mut input).unwrap();
+    assert_eq!(::parse(&input).unwrap(), ::parse_parallel(&input).unwrap());
+
+    // Records that span lines and errors are handled by decoding sequentially.
+    let input = b"ply\nformat ascii 1.0\nelement vertex 2\nproperty int x\nproperty int y\n\
+                  element face 1\nproperty list uchar int i\nend_header\n1\n2 3 4\n\n2 5 6\n";
+    assert_eq!(::parse(input).unwrap(), ::parse_parallel(input).unwrap());
+    let input = b"ply\nformat ascii 1.0\nelement vertex 2\nproperty int x\nend_header\n1\nx\n";
+    assert!(matches!(::parse_parallel(input),
+                     Err(PlyError::InvalidNumber { offset: 66, element: 0 })));
+    // Counts that overflow when added up are not mistaken for the number of lines.
+    let input = b"ply\nformat ascii 1.0\nelement a 9223372036854775807\nproperty int x\n\
+                  element b 9223372036854775807\nproperty int x\nelement c 3\nproperty int x\n\
+                  end_header\n1\n";
+    assert!(matches!(::parse_parallel(input), Err(PlyError::TruncatedBody { .. })));
+}
@@ -0,0 +1,63 @@
+use error::PlyError;
+use header::Header;
+
+/// The elements and properties to decode with 'Reader::read_projected'. Everything else is
+/// skipped without being decoded, but the structure of the body is still checked.
+#[derive(Debug, Clone, Default)]
+pub struct Projection {
+    // Names of the elements to keep, with the names of the properties to keep or 'None' for all.
+    elements: Vec<(String, Option<Vec<String>>)>,
+}
+
+impl Projection {
+    /// A projection that keeps nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps all properties of the element 'name'.
+    pub fn element(mut self, name: &str) -> Self {
+        self.elements.push((name.to_string(), None));
+        self
+    }
+
+    /// Keeps the properties 'properties' of the element 'name'. Records hold them in the order of
+    /// the header, not of 'properties'.
+    pub fn properties(mut self, name: &str, properties: &[&str]) -> Self {
+        let properties = properties.iter().map(|property| property.to_string()).collect();
+        self.elements.push((name.to_string(), Some(properties)));
+        self
+    }
+}
+
+/// For each element of 'header', whether each of its properties is kept, or 'None' if the element
+/// is skipped altogether.
+pub fn keep(projection: &Projection, header: &Header) -> Result<Vec<Option<Vec<bool>>>, PlyError> {
+    let mut keep = vec![None; header.elements.len()];
+    for (name, properties) in &projection.elements {
+        let index = header.elements
+            .iter()
+            .position(|element| element.name == *name)
+            .ok_or_else(|| PlyError::NoSuchElement { name: name.clone() })?;
+        let element = &header.elements[index];
+        let kept = keep[index].get_or_insert_with(|| vec![false; element.properties.len()]);
+        match properties {
+            None => kept.iter_mut().for_each(|kept| *kept = true),
+            Some(properties) => {
+                for name in properties {
+                    let property = element.properties
+                        .iter()
+                        .position(|property| property.name == *name)
+                        .ok_or_else(|| {
+                            PlyError::NoSuchProperty {
+                                element: index,
+                                name: name.clone(),
+                            }
+                        })?;
+                    kept[property] = true;
+                }
+            }
+        }
+    }
+    Ok(keep)
+}
@@ -0,0 +1,598 @@
+use access::{from_record, PropertyAccess};
+use body::{check_trailing, decode_projected, decode_record, skip_record, Ply, Record};
+use columns::{decode_columns, Columns};
+use error::PlyError;
+use header::{header, Element, FormatKind, Header};
+use options::ReadOptions;
+use projection::{keep, Projection};
+#[cfg(feature = "serde")]
+use de;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Seek, SeekFrom};
+
+// Seeks a reader ahead by a number of bytes.
+type SeekFn<R> = fn(&mut R, i64) -> io::Result<u64>;
+
+/// A pull-based reader that decodes one record at a time.
+///
+/// Only the header and the bytes of the record currently being decoded are kept in memory, so
+/// files of any size can be processed. Elements are handed out in declaration order. The input
+/// is read as is, wrap it with 'decompress' to also accept compressed files:
+///
+/// ```no_run
+/// # use std::fs::File;
+/// let file = ply::decompress(File::open("scan.ply.gz").unwrap()).unwrap();
+/// let mut reader = ply::Reader::new(file).unwrap();
+/// while let Some(mut element) = reader.next_element().unwrap() {
+///     println!("{}", element.element().name);
+///     for record in &mut element {
+///         let record = record.unwrap();
+///     }
+/// }
+/// ```
+pub struct Reader<R> {
+    reader: R,
+    header: Header,
+    // Bytes read from 'reader' that have not been decoded yet start at 'buffer[position]'.
+    buffer: Vec<u8>,
+    position: usize,
+    // File offset of 'buffer[0]', only used for error reporting.
+    offset: usize,
+    // Index of the element 'next_element' hands out next, and the number of records still to
+    // come in the one handed out before.
+    next_element: usize,
+    remaining: i64,
+    // For readers made with 'seekable': the length of the input and how to skip ahead in it.
+    seek: Option<(usize, SeekFn<R>)>,
+    // Set once the body has been read completely or an error occurred.
+    done: bool,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Reads and parses the header. The body is not touched until records are requested.
+    pub fn new(reader: R) -> Result<Self, PlyError> {
+        Self::with_options(reader, &ReadOptions::default())
+    }
+
+    pub fn with_options(mut reader: R, options: &ReadOptions) -> Result<Self, PlyError> {
+        let mut buffer = Vec::new();
+        loop {
+            let start = buffer.len();
+            if reader.read_until(b'\n', &mut buffer)? == 0 {
+                break;
+            }
+            // Do not read all of a file that is not a PLY file at all looking for 'end_header'.
+            if start == 0 && !buffer.starts_with(b"ply") {
+                return Err(PlyError::BadMagic);
+            }
+            let line = &buffer[start..];
+            let end = line.iter().rposition(|c| !b" \t\r\n".contains(c)).map_or(0, |end| end + 1);
+            if &line[..end] == b"end_header" {
+                break;
+            }
+        }
+        let header = header(&buffer, options)?.1;
+        Ok(Reader {
+            reader,
+            header,
+            buffer: Vec::new(),
+            position: 0,
+            offset: buffer.len(),
+            next_element: 0,
+            remaining: 0,
+            seek: None,
+            done: false,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn into_header(self) -> Header {
+        self.header
+    }
+
+    /// The file offset up to which the input has been consumed.
+    pub fn offset(&self) -> usize {
+        self.offset + self.position
+    }
+
+    /// Returns a reader for the records of the next element, or 'None' after the last one.
+    /// Records of the previous element that have not been consumed are skipped.
+    pub fn next_element(&mut self) -> Result<Option<ElementReader<'_, R>>, PlyError> {
+        self.skip_records(usize::MAX)?;
+        if self.done {
+            return Ok(None);
+        }
+        if self.next_element == self.header.elements.len() {
+            self.done = true;
+            self.check_end()?;
+            return Ok(None);
+        }
+        self.remaining = self.header.elements[self.next_element].count;
+        self.next_element += 1;
+        Ok(Some(ElementReader { reader: self }))
+    }
+
+    /// Reads the elements that have not been handed out by 'next_element' yet, but only decodes the
+    /// elements and properties 'projection' keeps. The records of other elements are skipped,
+    /// in bulk if they are binary and have a fixed size, with a seek for readers made with
+    /// 'seekable'. The properties that are not kept are stepped over. Skipped ASCII values are
+    /// split into tokens but not parsed.
+    ///
+    /// The header of the result only declares the elements and properties that were decoded, so
+    /// that it describes the records in the payload.
+    pub fn read_projected(mut self, projection: &Projection) -> Result<Ply, PlyError> {
+        let keep = keep(projection, &self.header)?;
+        let mut payload = HashMap::new();
+        while let Some(element) = self.next_element()? {
+            let index = element.reader.next_element - 1;
+            if let Some(ref kept) = keep[index] {
+                let mut records = Vec::new();
+                while element.reader.remaining > 0 && !element.reader.done {
+                    records.push(element.reader.next_projected(kept)?);
+                }
+                payload.insert(element.element().name.clone(), records);
+            }
+        }
+        let mut header = self.header;
+        let elements = header.elements.drain(..).zip(keep).filter_map(|(mut element, kept)| {
+            let kept = kept?;
+            if !payload.contains_key(&element.name) {
+                return None;
+            }
+            let mut kept = kept.into_iter();
+            element.properties.retain(|_| kept.next() == Some(true));
+            Some(element)
+        });
+        header.elements = elements.collect();
+        Ok(Ply { header, payload })
+    }
+
+    /// Appends more input to the buffer, returns the number of bytes read.
+    fn fill(&mut self) -> Result<usize, PlyError> {
+        // Everything before 'position' is decoded already, the buffer only keeps a partial record.
+        self.buffer.drain(..self.position);
+        self.offset += self.position;
+        self.position = 0;
+        // ASCII is read in whole lines, so that a number is never cut in two.
+        if self.header.format.kind == FormatKind::Ascii {
+            return Ok(self.reader.read_until(b'\n', &mut self.buffer)?);
+        }
+        let read = {
+            let available = self.reader.fill_buf()?;
+            self.buffer.extend_from_slice(available);
+            available.len()
+        };
+        self.reader.consume(read);
+        Ok(read)
+    }
+
+    fn next_record(&mut self) -> Result<Record, PlyError> {
+        self.next_with(|input, offset, header, index| {
+            decode_record(input, offset, header, index)
+                .map(|decoded| decoded.map(|(rest, record)| (input.len() - rest.len(), record)))
+        })
+    }
+
+    fn next_projected(&mut self, keep: &[bool]) -> Result<Record, PlyError> {
+        self.next_with(|input, offset, header, index| {
+            decode_projected(input, offset, header, index, keep)
+                .map(|decoded| decoded.map(|(rest, record)| (input.len() - rest.len(), record)))
+        })
+    }
+
+    /// Skips up to 'count' records of the current element, returns the number skipped. Binary
+    /// records of a fixed size are skipped all at once.
+    fn skip_records(&mut self, count: usize) -> Result<usize, PlyError> {
+        if self.done || self.remaining <= 0 {
+            return Ok(0);
+        }
+        let count = count.min(self.remaining as usize);
+        let index = self.next_element - 1;
+        let size = match self.header.format.kind {
+            FormatKind::Ascii => None,
+            _ => self.header.elements[index].record_size(),
+        };
+        match size {
+            Some(size) => {
+                let (start, len) = (self.offset(), count.saturating_mul(size));
+                let skipped = self.skip_bytes(len)?;
+                if skipped < len {
+                    self.done = true;
+                    return Err(PlyError::TruncatedBody {
+                        offset: start + skipped / size * size,
+                        element: index,
+                    });
+                }
+                self.remaining -= count as i64;
+            }
+            None => {
+                for _ in 0..count {
+                    self.skip_record()?;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Consumes up to 'len' bytes of input without looking at them, returns the number consumed,
+    /// which is less than 'len' only at the end of the input. Readers made with 'seekable' seek
+    /// past the bytes that are not buffered, others read them.
+    fn skip_bytes(&mut self, len: usize) -> Result<usize, PlyError> {
+        let buffered = (self.buffer.len() - self.position).min(len);
+        self.position += buffered;
+        if buffered == len {
+            return Ok(len);
+        }
+        // The buffer is used up, skip the rest straight in 'reader'.
+        self.offset += self.buffer.len();
+        self.buffer.clear();
+        self.position = 0;
+        let mut skipped = buffered;
+        if let Some((end, seek)) = self.seek {
+            let step = (len - skipped).min(end.saturating_sub(self.offset));
+            if let Err(err) = seek(&mut self.reader, step as i64) {
+                self.done = true;
+                return Err(err.into());
+            }
+            self.offset += step;
+            return Ok(skipped + step);
+        }
+        while skipped < len {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available.len(),
+                Err(err) => {
+                    self.done = true;
+                    return Err(err.into());
+                }
+            };
+            if available == 0 {
+                break;
+            }
+            let step = available.min(len - skipped);
+            self.reader.consume(step);
+            self.offset += step;
+            skipped += step;
+        }
+        Ok(skipped)
+    }
+
+    fn skip_record(&mut self) -> Result<(), PlyError> {
+        self.next_with(|input, offset, header, index| {
+            skip_record(input, offset, header, index).map(|skipped| skipped.map(|len| (len, ())))
+        })
+    }
+
+    /// Decodes the next record with 'decode', which is called with the buffered input, its file
+    /// offset, the header and the element index, and returns the number of bytes it consumed
+    /// and its result, or 'None' if the input ends before the record does.
+    fn next_with<T, F>(&mut self, mut decode: F) -> Result<T, PlyError>
+        where F: FnMut(&[u8], usize, &Header, usize) -> Result<Option<(usize, T)>, PlyError>
+    {
+        let index = self.next_element - 1;
+        loop {
+            let offset = self.offset + self.position;
+            let decoded = decode(&self.buffer[self.position..], offset, &self.header, index);
+            match decoded {
+                Ok(Some((consumed, result))) => {
+                    self.position += consumed;
+                    self.remaining -= 1;
+                    return Ok(result);
+                }
+                Ok(None) => {
+                    match self.fill() {
+                        Ok(0) => {
+                            self.done = true;
+                            return Err(PlyError::TruncatedBody {
+                                offset,
+                                element: index,
+                            });
+                        }
+                        Ok(_) => (),
+                        Err(err) => {
+                            self.done = true;
+                            return Err(err);
+                        }
+                    }
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Makes sure that nothing but whitespace follows the last record.
+    fn check_end(&mut self) -> Result<(), PlyError> {
+        loop {
+            check_trailing(&self.buffer[self.position..],
+                           self.offset + self.position,
+                           &self.header)?;
+            self.position = self.buffer.len();
+            if self.fill()? == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<R: BufRead + Seek> Reader<R> {
+    /// Like 'with_options', but fixed-size binary records that are skipped, e.g. by
+    /// 'read_projected', are seeked over instead of read. The input starts at the current
+    /// position of 'reader'.
+    pub fn seekable(mut reader: R, options: &ReadOptions) -> Result<Self, PlyError> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+        let mut reader = Self::with_options(reader, options)?;
+        let len = (end - start) as usize;
+        reader.seek = Some((len, |reader, step| reader.seek(SeekFrom::Current(step))));
+        Ok(reader)
+    }
+}
+
+/// Skips 'skip' records of the element at 'index' and decodes the following 'count' from 'reader',
+/// which is positioned at file offset 'offset', the start of a record.
+pub fn read_records_at<R: BufRead>(reader: R,
+                                   header: Header,
+                                   offset: usize,
+                                   index: usize,
+                                   skip: usize,
+                                   count: usize)
+                                   -> Result<Vec<Record>, PlyError> {
+    let mut reader = Reader {
+        reader,
+        header,
+        buffer: Vec::new(),
+        position: 0,
+        offset,
+        next_element: index + 1,
+        remaining: (skip + count) as i64,
+        seek: None,
+        done: false,
+    };
+    let mut records = ElementReader { reader: &mut reader };
+    records.skip_records(skip)?;
+    records.collect()
+}
+
+/// Iterates over the records of one element, see 'Reader::next_element'.
+pub struct ElementReader<'a, R: 'a> {
+    reader: &'a mut Reader<R>,
+}
+
+impl<'a, R: BufRead> ElementReader<'a, R> {
+    pub fn element(&self) -> &Element {
+        &self.reader.header.elements[self.reader.next_element - 1]
+    }
+
+    /// The file offset of the next record.
+    pub fn offset(&self) -> usize {
+        self.reader.offset()
+    }
+
+    /// Skips up to 'count' records without decoding them where possible, returns the number
+    /// skipped. Binary records of a fixed size are skipped all at once.
+    pub fn skip_records(&mut self, count: usize) -> Result<usize, PlyError> {
+        self.reader.skip_records(count)
+    }
+
+    /// Reads the remaining records of the element into a 'T' each. Fails without reading a record
+    /// if 'PropertyAccess::check' rejects the element.
+    pub fn read_into<T: PropertyAccess>(&mut self) -> Result<Vec<T>, PlyError> {
+        T::check(self.element()).map_err(|property| {
+            PlyError::IncompatibleProperty {
+                element: self.reader.next_element - 1,
+                property,
+            }
+        })?;
+        let mut result = Vec::new();
+        while let Some(record) = self.next() {
+            result.push(from_record(self.element(), record?));
+        }
+        Ok(result)
+    }
+
+    /// Reads the remaining records of the element into one column per property. Binary records
+    /// are decoded straight into the columns.
+    pub fn read_columns(&mut self) -> Result<Columns, PlyError> {
+        let mut columns = Columns::new(self.element());
+        while self.reader.remaining > 0 && !self.reader.done {
+            self.reader.next_with(|input, offset, header, index| {
+                let decoded = decode_columns(input, offset, header, index, &mut columns)?;
+                Ok(decoded.map(|consumed| (consumed, ())))
+            })?;
+        }
+        Ok(columns)
+    }
+
+    /// Deserializes the remaining records of the element into a 'T' each, see 'from_record'.
+    #[cfg(feature = "serde")]
+    pub fn deserialize_into<T: DeserializeOwned>(&mut self) -> Result<Vec<T>, PlyError> {
+        let mut result = Vec::new();
+        while let Some(record) = self.next() {
+            result.push(de::from_record(self.element(), record?)?);
+        }
+        Ok(result)
+    }
+}
+
+impl<'a, R: BufRead> Iterator for ElementReader<'a, R> {
+    type Item = Result<Record, PlyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.remaining == 0 || self.reader.done {
+            return None;
+        }
+        Some(self.reader.next_record())
+    }
+}
+
+#[test]
+fn reader_test() {
+    use body::{PropertyValue, Value};
+    use std::fs::File;
+    use std::io::BufReader;
+
+    // A tiny buffer makes sure that records regularly straddle refills.
+    let file = File::open("testdata/beethoven.ply").unwrap();
+    let mut reader = Reader::new(BufReader::with_capacity(7, file)).unwrap();
+    assert_eq!(2, reader.header().elements.len());
+    {
+        // Only look at the first vertex, the rest is skipped.
+        let mut vertices = reader.next_element().unwrap().unwrap();
+        assert_eq!("vertex", vertices.element().name);
+        assert_eq!(PropertyValue::Scalar(Value::Float32(-0.093362)),
+                   vertices.next().unwrap().unwrap()[0]);
+    }
+    let faces = reader.next_element().unwrap().unwrap();
+    assert_eq!("face", faces.element().name);
+    assert_eq!(5030, faces.map(Result::unwrap).count());
+    assert!(reader.next_element().unwrap().is_none());
+}
+
+#[test]
+fn read_into_test() {
+    use body::{PropertyValue, Value};
+
+    #[derive(Debug, PartialEq)]
+    struct Face {
+        indices: Vec<Value>,
+        flagged: bool,
+    }
+
+    impl PropertyAccess for Face {
+        fn new() -> Self {
+            Face { indices: Vec::new(), flagged: false }
+        }
+
+        fn set_property(&mut self, name: &str, value: PropertyValue) {
+            match (name, value) {
+                ("vertex_indices", PropertyValue::List(indices)) => self.indices = indices,
+                ("flags", PropertyValue::Scalar(flags)) => self.flagged = flags != Value::UInt8(0),
+                _ => (),
+            }
+        }
+    }
+
+    let input = b"ply\nformat ascii 1.0\nelement face 2\nproperty uchar flags\n\
+                  property list uchar int vertex_indices\nproperty float unused\nend_header\n\
+                  0 3 0 1 2 0.5\n1 1 7 0.5\n";
+    let mut reader = Reader::new(&input[..]).unwrap();
+    let faces = reader.next_element().unwrap().unwrap().read_into::<Face>().unwrap();
+    assert_eq!(vec![Face { indices: vec![Value::Int32(0), Value::Int32(1), Value::Int32(2)],
+                           flagged: false },
+                    Face { indices: vec![Value::Int32(7)], flagged: true }],
+               faces);
+}
+
+#[test]
+fn read_columns_test() {
+    use columns::{Column, PropertyColumn};
+    use std::fs::File;
+    use std::io::BufReader;
+
+    let file = File::open("testdata/beethoven.ply").unwrap();
+    let mut reader = Reader::new(BufReader::with_capacity(7, file)).unwrap();
+    let vertices = reader.next_element().unwrap().unwrap().read_columns().unwrap();
+    assert_eq!(2521, vertices.len());
+    assert_eq!(-0.093362, vertices.scalar::<f32>("x").unwrap()[0]);
+    let faces = reader.next_element().unwrap().unwrap().read_columns().unwrap();
+    match faces.get("vertex_indices") {
+        Some(PropertyColumn::List { offsets, values: Column::Int32(values) }) => {
+            assert_eq!(5031, offsets.len());
+            assert_eq!(&[850, 2520, 2515], &values[offsets[5029]..offsets[5030]]);
+        }
+        other => panic!("unexpected column {:?}", other),
+    }
+    assert!(reader.next_element().unwrap().is_none());
+}
+
+#[test]
+fn reader_binary_test() {
+    let input = b"ply\nformat binary_big_endian 1.0\nelement vertex 2\n\
+                  property list uchar ushort i\nend_header\n\x02\x00\x01\x00\x02\x01\x00";
+    let mut reader = Reader::new(&input[..]).unwrap();
+    let records = reader.next_element().unwrap().unwrap().collect::<Vec<_>>();
+    assert_eq!(2, records.len());
+    assert!(records[0].is_ok());
+    assert!(matches!(records[1], Err(PlyError::TruncatedBody { offset: 95, element: 0 })));
+    assert!(reader.next_element().unwrap().is_none());
+}
+
+#[test]
+fn read_projected_test() {
+    use body::{PropertyValue, Value};
+    use std::fs::File;
+    use std::io::{BufReader, Cursor, Read};
+
+    let file = BufReader::with_capacity(7, File::open("testdata/beethoven.ply").unwrap());
+    let projection = Projection::new().properties("vertex", &["z", "x"]);
+    let ply = Reader::new(file).unwrap().read_projected(&projection).unwrap();
+    assert_eq!(1, ply.header.elements.len());
+    let names = ply.header.elements[0].properties.iter().map(|p| &p.name[..]).collect::<Vec<_>>();
+    assert_eq!(vec!["x", "z"], names);
+    assert!(!ply.payload.contains_key("face"));
+    let vertices = &ply.payload["vertex"];
+    assert_eq!(2521, vertices.len());
+    assert_eq!(2, vertices[0].len());
+    assert_eq!(PropertyValue::Scalar(Value::Float32(-0.093362)), vertices[0][0]);
+
+    // The fixed size vertices of a binary file are skipped in one go.
+    let mut full = ::read(File::open("testdata/beethoven.ply").unwrap()).unwrap();
+    full.header.format.kind = FormatKind::LittleEndian;
+    let mut binary = Vec::new();
+    ::write(&mut binary, &full).unwrap();
+    let projection = Projection::new().element("face");
+    let ply = Reader::new(&binary[..]).unwrap().read_projected(&projection).unwrap();
+    assert_eq!(full.payload["face"], ply.payload["face"]);
+
+    // Seekable readers do not read the skipped vertices at all.
+    struct Counted<'a>(Cursor<&'a [u8]>, usize);
+    impl<'a> Read for Counted<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = self.0.read(buf)?;
+            self.1 += read;
+            Ok(read)
+        }
+    }
+    impl<'a> Seek for Counted<'a> {
+        fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+            self.0.seek(position)
+        }
+    }
+    let mut counted = BufReader::with_capacity(64, Counted(Cursor::new(&binary[..]), 0));
+    let ply = Reader::seekable(&mut counted, &ReadOptions::default())
+        .unwrap()
+        .read_projected(&projection)
+        .unwrap();
+    assert_eq!(full.payload["face"], ply.payload["face"]);
+    // Only the header, the faces and what the last buffer fill read ahead of them.
+    assert!(counted.get_ref().1 < binary.len() - 2521 * 12 + 64);
+
+    let projection = Projection::new().properties("vertex", &["w"]);
+    assert!(matches!(Reader::new(&binary[..]).unwrap().read_projected(&projection),
+                     Err(PlyError::NoSuchProperty { element: 0, .. })));
+}
+
+#[test]
+fn skip_truncated_test() {
+    let input = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty short x\n\
+                  element face 0\nproperty uchar flags\nend_header\n\x01\x00\x02\x00\x03";
+    let projection = Projection::new().element("face");
+    assert!(matches!(Reader::new(&input[..]).unwrap().read_projected(&projection),
+                     Err(PlyError::TruncatedBody { offset: 121, element: 0 })));
+    let seekable = Reader::seekable(::std::io::Cursor::new(&input[..]), &ReadOptions::default());
+    assert!(matches!(seekable.unwrap().read_projected(&projection),
+                     Err(PlyError::TruncatedBody { offset: 121, element: 0 })));
+    // Skipped ASCII values are not parsed, but their number still has to match.
+    let input = b"ply\nformat ascii 1.0\nelement vertex 2\nproperty list uchar int i\n\
+                  end_header\n2 a b\n3 c d\n";
+    assert!(matches!(Reader::new(&input[..]).unwrap().read_projected(&Projection::new()),
+                     Err(PlyError::TruncatedBody { element: 0, .. })));
+}
@@ -0,0 +1,621 @@
+use body::{PropertyValue, Record, Value};
+use error::PlyError;
+use header::{Element, Property, PropertyKind, ValueKind};
+use serde::ser::{self, Impossible, Serialize, SerializeSeq, SerializeStruct, SerializeTuple,
+                 SerializeTupleStruct, Serializer};
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+impl ser::Error for PlyError {
+    fn custom<T: Display>(msg: T) -> Self {
+        PlyError::Serde(msg.to_string())
+    }
+}
+
+fn out_of_range<T: Display>(value: T, kind: ValueKind) -> PlyError {
+    PlyError::Serde(format!("{} does not fit a property of type {:?}", value, kind))
+}
+
+/// Converts 'v' to a value of 'kind', failing unless it is represented exactly.
+fn integer(kind: ValueKind, v: i128) -> Result<Value, PlyError> {
+    let value = match kind {
+        ValueKind::Int8 => i8::try_from(v).ok().map(Value::Int8),
+        ValueKind::UInt8 => u8::try_from(v).ok().map(Value::UInt8),
+        ValueKind::Int16 => i16::try_from(v).ok().map(Value::Int16),
+        ValueKind::UInt16 => u16::try_from(v).ok().map(Value::UInt16),
+        ValueKind::Int32 => i32::try_from(v).ok().map(Value::Int32),
+        ValueKind::UInt32 => u32::try_from(v).ok().map(Value::UInt32),
+        ValueKind::Int64 => i64::try_from(v).ok().map(Value::Int64),
+        ValueKind::UInt64 => u64::try_from(v).ok().map(Value::UInt64),
+        ValueKind::Float32 => Some(v as f32).filter(|&f| f as i128 == v).map(Value::Float32),
+        ValueKind::Float64 => Some(v as f64).filter(|&f| f as i128 == v).map(Value::Float64),
+    };
+    value.ok_or_else(|| out_of_range(v, kind))
+}
+
+/// Converts 'v' to a value of 'kind'. Narrowing to 'Float32' rounds, integer kinds only take
+/// whole numbers in range.
+fn float(kind: ValueKind, v: f64) -> Result<Value, PlyError> {
+    match kind {
+        ValueKind::Float32 if v.is_finite() && (v as f32).is_infinite() => {
+            Err(out_of_range(v, kind))
+        }
+        ValueKind::Float32 => Ok(Value::Float32(v as f32)),
+        ValueKind::Float64 => Ok(Value::Float64(v)),
+        _ if v.fract() == 0.0 => integer(kind, v as i128).map_err(|_| out_of_range(v, kind)),
+        _ => Err(out_of_range(v, kind)),
+    }
+}
+
+/// Implements the 'Serializer' methods that take a plain value by rejecting it.
+macro_rules! reject {
+    ($($method:ident($($ty:ty),*);)*) => {
+        $(
+            fn $method(self, $(_: $ty),*) -> Result<Self::Ok, PlyError> {
+                Err(self.reject(stringify!($method)))
+            }
+        )*
+    }
+}
+
+/// Implements the 'Serializer' methods that take a number by delegating to 'self.scalar()'.
+macro_rules! scalar_numbers {
+    ($($method:ident($ty:ty);)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<PropertyValue, PlyError> {
+                self.scalar()?.$method(v).map(PropertyValue::Scalar)
+            }
+        )*
+    }
+}
+
+/// Serializes a number into a value of the declared type.
+struct ValueSerializer(ValueKind);
+
+impl ValueSerializer {
+    fn reject(&self, method: &str) -> PlyError {
+        PlyError::Serde(format!("{} cannot be a property value", &method["serialize_".len()..]))
+    }
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = PlyError;
+    type SerializeSeq = Impossible<Value, PlyError>;
+    type SerializeTuple = Impossible<Value, PlyError>;
+    type SerializeTupleStruct = Impossible<Value, PlyError>;
+    type SerializeTupleVariant = Impossible<Value, PlyError>;
+    type SerializeMap = Impossible<Value, PlyError>;
+    type SerializeStruct = Impossible<Value, PlyError>;
+    type SerializeStructVariant = Impossible<Value, PlyError>;
+
+    fn serialize_i8(self, v: i8) -> Result<Value, PlyError> {
+        integer(self.0, v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, PlyError> {
+        integer(self.0, v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, PlyError> {
+        integer(self.0, v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, PlyError> {
+        integer(self.0, v.into())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, PlyError> {
+        integer(self.0, v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, PlyError> {
+        integer(self.0, v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, PlyError> {
+        integer(self.0, v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, PlyError> {
+        integer(self.0, v.into())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, PlyError> {
+        float(self.0, v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, PlyError> {
+        float(self.0, v)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, PlyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self,
+                                                       _name: &'static str,
+                                                       value: &T)
+                                                       -> Result<Value, PlyError> {
+        value.serialize(self)
+    }
+
+    reject! {
+        serialize_bool(bool);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self,
+                                                        _name: &'static str,
+                                                        _index: u32,
+                                                        _variant: &'static str,
+                                                        _value: &T)
+                                                        -> Result<Value, PlyError> {
+        Err(self.reject("serialize_newtype_variant"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, PlyError> {
+        Err(self.reject("serialize_seq"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, PlyError> {
+        Err(self.reject("serialize_tuple"))
+    }
+
+    fn serialize_tuple_struct(self,
+                              _name: &'static str,
+                              _len: usize)
+                              -> Result<Self::SerializeTupleStruct, PlyError> {
+        Err(self.reject("serialize_tuple_struct"))
+    }
+
+    fn serialize_tuple_variant(self,
+                               _name: &'static str,
+                               _index: u32,
+                               _variant: &'static str,
+                               _len: usize)
+                               -> Result<Self::SerializeTupleVariant, PlyError> {
+        Err(self.reject("serialize_tuple_variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, PlyError> {
+        Err(self.reject("serialize_map"))
+    }
+
+    fn serialize_struct(self,
+                        _name: &'static str,
+                        _len: usize)
+                        -> Result<Self::SerializeStruct, PlyError> {
+        Err(self.reject("serialize_struct"))
+    }
+
+    fn serialize_struct_variant(self,
+                                _name: &'static str,
+                                _index: u32,
+                                _variant: &'static str,
+                                _len: usize)
+                                -> Result<Self::SerializeStructVariant, PlyError> {
+        Err(self.reject("serialize_struct_variant"))
+    }
+}
+
+/// Serializes a number or a sequence of numbers into a value of 'property'.
+struct PropertySerializer<'a>(&'a Property);
+
+impl<'a> PropertySerializer<'a> {
+    fn reject(&self, method: &str) -> PlyError {
+        PlyError::Serde(format!("{} cannot be the value of property '{}'",
+                                &method["serialize_".len()..],
+                                self.0.name))
+    }
+
+    fn scalar(&self) -> Result<ValueSerializer, PlyError> {
+        match self.0.kind {
+            PropertyKind::Scalar(kind) => Ok(ValueSerializer(kind)),
+            PropertyKind::List(..) => {
+                Err(PlyError::Serde(format!("list property '{}' needs a sequence", self.0.name)))
+            }
+        }
+    }
+
+    fn list(&self, len: Option<usize>) -> Result<ListSerializer, PlyError> {
+        match self.0.kind {
+            PropertyKind::Scalar(_) => Err(self.reject("serialize_seq")),
+            PropertyKind::List(_, kind) => {
+                Ok(ListSerializer {
+                    kind,
+                    values: Vec::with_capacity(len.unwrap_or(0)),
+                })
+            }
+        }
+    }
+}
+
+impl<'a> Serializer for PropertySerializer<'a> {
+    type Ok = PropertyValue;
+    type Error = PlyError;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = Impossible<PropertyValue, PlyError>;
+    type SerializeTupleVariant = Impossible<PropertyValue, PlyError>;
+    type SerializeMap = Impossible<PropertyValue, PlyError>;
+    type SerializeStruct = Impossible<PropertyValue, PlyError>;
+    type SerializeStructVariant = Impossible<PropertyValue, PlyError>;
+
+    scalar_numbers! {
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<PropertyValue, PlyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self,
+                                                       _name: &'static str,
+                                                       value: &T)
+                                                       -> Result<PropertyValue, PlyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, PlyError> {
+        self.list(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer, PlyError> {
+        self.list(Some(len))
+    }
+
+    reject! {
+        serialize_bool(bool);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self,
+                                                        _name: &'static str,
+                                                        _index: u32,
+                                                        _variant: &'static str,
+                                                        _value: &T)
+                                                        -> Result<PropertyValue, PlyError> {
+        Err(self.reject("serialize_newtype_variant"))
+    }
+
+    fn serialize_tuple_struct(self,
+                              _name: &'static str,
+                              _len: usize)
+                              -> Result<Self::SerializeTupleStruct, PlyError> {
+        Err(self.reject("serialize_tuple_struct"))
+    }
+
+    fn serialize_tuple_variant(self,
+                               _name: &'static str,
+                               _index: u32,
+                               _variant: &'static str,
+                               _len: usize)
+                               -> Result<Self::SerializeTupleVariant, PlyError> {
+        Err(self.reject("serialize_tuple_variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, PlyError> {
+        Err(self.reject("serialize_map"))
+    }
+
+    fn serialize_struct(self,
+                        _name: &'static str,
+                        _len: usize)
+                        -> Result<Self::SerializeStruct, PlyError> {
+        Err(self.reject("serialize_struct"))
+    }
+
+    fn serialize_struct_variant(self,
+                                _name: &'static str,
+                                _index: u32,
+                                _variant: &'static str,
+                                _len: usize)
+                                -> Result<Self::SerializeStructVariant, PlyError> {
+        Err(self.reject("serialize_struct_variant"))
+    }
+}
+
+/// Collects the items of a list property.
+struct ListSerializer {
+    kind: ValueKind,
+    values: Vec<Value>,
+}
+
+impl SerializeSeq for ListSerializer {
+    type Ok = PropertyValue;
+    type Error = PlyError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), PlyError> {
+        self.values.push(value.serialize(ValueSerializer(self.kind))?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<PropertyValue, PlyError> {
+        Ok(PropertyValue::List(self.values))
+    }
+}
+
+impl SerializeTuple for ListSerializer {
+    type Ok = PropertyValue;
+    type Error = PlyError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), PlyError> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<PropertyValue, PlyError> {
+        SerializeSeq::end(self)
+    }
+}
+
+/// Serializes a struct, whose fields are matched to properties by name, or a tuple or sequence,
+/// whose items are the properties in declaration order, into a record of 'element'.
+struct RecordSerializer<'a>(&'a Element);
+
+impl<'a> RecordSerializer<'a> {
+    fn reject(&self, method: &str) -> PlyError {
+        PlyError::Serde(format!("{} cannot be a record of element '{}'",
+                                &method["serialize_".len()..],
+                                self.0.name))
+    }
+
+    fn fields(self) -> FieldSerializer<'a> {
+        FieldSerializer {
+            element: self.0,
+            values: self.0.properties.iter().map(|_| None).collect(),
+            next: 0,
+        }
+    }
+}
+
+impl<'a> Serializer for RecordSerializer<'a> {
+    type Ok = Record;
+    type Error = PlyError;
+    type SerializeSeq = FieldSerializer<'a>;
+    type SerializeTuple = FieldSerializer<'a>;
+    type SerializeTupleStruct = FieldSerializer<'a>;
+    type SerializeTupleVariant = Impossible<Record, PlyError>;
+    type SerializeMap = Impossible<Record, PlyError>;
+    type SerializeStruct = FieldSerializer<'a>;
+    type SerializeStructVariant = Impossible<Record, PlyError>;
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self,
+                                                       _name: &'static str,
+                                                       value: &T)
+                                                       -> Result<Record, PlyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<FieldSerializer<'a>, PlyError> {
+        Ok(self.fields())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<FieldSerializer<'a>, PlyError> {
+        Ok(self.fields())
+    }
+
+    fn serialize_tuple_struct(self,
+                              _name: &'static str,
+                              _len: usize)
+                              -> Result<FieldSerializer<'a>, PlyError> {
+        Ok(self.fields())
+    }
+
+    fn serialize_struct(self,
+                        _name: &'static str,
+                        _len: usize)
+                        -> Result<FieldSerializer<'a>, PlyError> {
+        Ok(self.fields())
+    }
+
+    reject! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<Record, PlyError> {
+        Err(self.reject("serialize_some"))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self,
+                                                        _name: &'static str,
+                                                        _index: u32,
+                                                        _variant: &'static str,
+                                                        _value: &T)
+                                                        -> Result<Record, PlyError> {
+        Err(self.reject("serialize_newtype_variant"))
+    }
+
+    fn serialize_tuple_variant(self,
+                               _name: &'static str,
+                               _index: u32,
+                               _variant: &'static str,
+                               _len: usize)
+                               -> Result<Self::SerializeTupleVariant, PlyError> {
+        Err(self.reject("serialize_tuple_variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, PlyError> {
+        Err(self.reject("serialize_map"))
+    }
+
+    fn serialize_struct_variant(self,
+                                _name: &'static str,
+                                _index: u32,
+                                _variant: &'static str,
+                                _len: usize)
+                                -> Result<Self::SerializeStructVariant, PlyError> {
+        Err(self.reject("serialize_struct_variant"))
+    }
+}
+
+/// Collects the property values of a record, by name or by position.
+struct FieldSerializer<'a> {
+    element: &'a Element,
+    values: Vec<Option<PropertyValue>>,
+    // The property the next positional item is for.
+    next: usize,
+}
+
+impl<'a> FieldSerializer<'a> {
+    fn set<T: ?Sized + Serialize>(&mut self, index: usize, value: &T) -> Result<(), PlyError> {
+        let property = &self.element.properties[index];
+        self.values[index] = Some(value.serialize(PropertySerializer(property))?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Record, PlyError> {
+        let element = self.element;
+        self.values
+            .into_iter()
+            .zip(&element.properties)
+            .map(|(value, property)| {
+                value.ok_or_else(|| {
+                    PlyError::Serde(format!("no value for property '{}' of element '{}'",
+                                            property.name,
+                                            element.name))
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'a> SerializeStruct for FieldSerializer<'a> {
+    type Ok = Record;
+    type Error = PlyError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self,
+                                              key: &'static str,
+                                              value: &T)
+                                              -> Result<(), PlyError> {
+        match self.element.properties.iter().position(|property| property.name == key) {
+            Some(index) => self.set(index, value),
+            None => {
+                Err(PlyError::Serde(format!("element '{}' has no property '{}'",
+                                            self.element.name,
+                                            key)))
+            }
+        }
+    }
+
+    fn end(self) -> Result<Record, PlyError> {
+        FieldSerializer::end(self)
+    }
+}
+
+impl<'a> SerializeSeq for FieldSerializer<'a> {
+    type Ok = Record;
+    type Error = PlyError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), PlyError> {
+        let index = self.next;
+        if index == self.element.properties.len() {
+            return Err(PlyError::Serde(format!("element '{}' has only {} properties",
+                                               self.element.name,
+                                               index)));
+        }
+        self.next += 1;
+        self.set(index, value)
+    }
+
+    fn end(self) -> Result<Record, PlyError> {
+        FieldSerializer::end(self)
+    }
+}
+
+impl<'a> SerializeTuple for FieldSerializer<'a> {
+    type Ok = Record;
+    type Error = PlyError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), PlyError> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Record, PlyError> {
+        FieldSerializer::end(self)
+    }
+}
+
+impl<'a> SerializeTupleStruct for FieldSerializer<'a> {
+    type Ok = Record;
+    type Error = PlyError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), PlyError> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Record, PlyError> {
+        FieldSerializer::end(self)
+    }
+}
+
+/// Serializes each of 'items' into a record of 'element', converting numbers to the declared
+/// property types. Struct fields are matched to properties by name, tuples and sequences give
+/// the properties in declaration order, and every property needs a value.
+pub fn to_records<I>(element: &Element, items: I) -> Result<Vec<Record>, PlyError>
+    where I: IntoIterator,
+          I::Item: Serialize
+{
+    items.into_iter().map(|item| item.serialize(RecordSerializer(element))).collect()
+}
+
+#[test]
+fn to_records_test() {
+    #[derive(Serialize)]
+    struct Vertex {
+        indices: Vec<u32>,
+        x: f64,
+    }
+
+    let element = ::de::test_element();
+    let vertices = vec![Vertex { indices: vec![1, 2], x: 0.5 }];
+    assert_eq!(vec![vec![PropertyValue::Scalar(Value::Float32(0.5)),
+                         PropertyValue::List(vec![Value::UInt8(1), Value::UInt8(2)])]],
+               to_records(&element, &vertices).unwrap());
+    assert_eq!(vec![vec![PropertyValue::Scalar(Value::Float32(3.0)),
+                         PropertyValue::List(vec![])]],
+               to_records(&element, vec![(3, Vec::<u8>::new())]).unwrap());
+    // 256 does not fit the 'uchar' items and 0.5 is not a whole number.
+    assert!(matches!(to_records(&element, vec![(0.0, vec![256])]), Err(PlyError::Serde(_))));
+    assert!(matches!(to_records(&element, vec![(0.0, vec![0.5])]), Err(PlyError::Serde(_))));
+    // Every property needs a value.
+    assert!(matches!(to_records(&element, vec![(0.0,)]), Err(PlyError::Serde(_))));
+}
@@ -0,0 +1,153 @@
+use header::{is_strict_name, layout, Header, Line, PropertyKind, ValueKind};
+use std::collections::HashSet;
+use std::fmt;
+
+/// A problem found by 'Header::validate'.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// An element with this name was declared before.
+    DuplicateElement(String),
+    /// The element already has a property with this name.
+    DuplicateProperty(String),
+    /// The element has no properties.
+    NoProperties(String),
+    /// The list property has a floating point count type.
+    FloatListCount(String),
+    /// The name is not made up of ASCII letters, digits and '_', so other tools may reject it.
+    /// This is only a warning.
+    UnusualName(String),
+}
+
+/// An 'Issue' and the 1-based header line it was found on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub issue: Issue,
+}
+
+impl Diagnostic {
+    /// Whether the issue makes the header unusable rather than just unusual.
+    pub fn is_error(&self) -> bool {
+        !matches!(self.issue, Issue::UnusualName(_))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = if self.is_error() { "error" } else { "warning" };
+        write!(f, "line {}: {}: ", self.line, severity)?;
+        match self.issue {
+            Issue::DuplicateElement(ref name) => write!(f, "duplicate element '{}'", name),
+            Issue::DuplicateProperty(ref name) => write!(f, "duplicate property '{}'", name),
+            Issue::NoProperties(ref name) => write!(f, "element '{}' has no properties", name),
+            Issue::FloatListCount(ref name) => {
+                write!(f, "list property '{}' has a floating point count type", name)
+            }
+            Issue::UnusualName(ref name) => write!(f, "unusual name '{}'", name),
+        }
+    }
+}
+
+impl Header {
+    /// Checks the semantic rules the parser does not enforce and returns every problem found, in
+    /// header order. Diagnostics carry the line of the declaration in the parsed file, or the
+    /// line 'write_header' would put it on for elements and properties that were not parsed.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut elements = HashSet::new();
+        let mut properties = HashSet::new();
+        for (index, line) in layout(self).into_iter().enumerate() {
+            let declared = match line {
+                Line::Element(element) => element.line,
+                Line::Property(property) => property.line,
+                _ => 0,
+            };
+            let at = if declared == 0 { index + 1 } else { declared };
+            let mut report = |issue| diagnostics.push(Diagnostic { line: at, issue });
+            match line {
+                Line::Element(element) => {
+                    if !elements.insert(&element.name) {
+                        report(Issue::DuplicateElement(element.name.clone()));
+                    }
+                    if element.properties.is_empty() {
+                        report(Issue::NoProperties(element.name.clone()));
+                    }
+                    if !is_strict_name(&element.name) {
+                        report(Issue::UnusualName(element.name.clone()));
+                    }
+                    properties.clear();
+                }
+                Line::Property(property) => {
+                    if !properties.insert(&property.name) {
+                        report(Issue::DuplicateProperty(property.name.clone()));
+                    }
+                    if let PropertyKind::List(ValueKind::Float32, _) |
+                           PropertyKind::List(ValueKind::Float64, _) = property.kind {
+                        report(Issue::FloatListCount(property.name.clone()));
+                    }
+                    if !is_strict_name(&property.name) {
+                        report(Issue::UnusualName(property.name.clone()));
+                    }
+                }
+                _ => (),
+            }
+        }
+        diagnostics
+    }
+}
+
+#[test]
+fn validate_test() {
+    let input = b"ply\nformat ascii 1.0\nelement vertex 0\nproperty float x\ncomment between\n\
+                  property float x\nelement point 0\nelement face 0\n\
+                  property list float int vertex-index\nend_header\n";
+    // The parser rejects duplicate elements, so the second one is renamed after parsing.
+    let (_, mut header) = ::header::header(input, &Default::default()).unwrap();
+    header.elements[1].name = "vertex".to_string();
+    let diagnostics = header.validate();
+    assert_eq!(vec![Diagnostic { line: 6, issue: Issue::DuplicateProperty("x".to_string()) },
+                    Diagnostic { line: 7, issue: Issue::DuplicateElement("vertex".to_string()) },
+                    Diagnostic { line: 7, issue: Issue::NoProperties("vertex".to_string()) },
+                    Diagnostic {
+                        line: 9,
+                        issue: Issue::FloatListCount("vertex-index".to_string()),
+                    },
+                    Diagnostic { line: 9, issue: Issue::UnusualName("vertex-index".to_string()) }],
+               diagnostics);
+    assert_eq!("line 9: warning: unusual name 'vertex-index'", diagnostics[4].to_string());
+
+    // Blank lines count, like in the errors of the parser.
+    let input = b"ply\nformat ascii 1.0\n\nelement vertex 0\nproperty float x\nproperty float x\n\
+                  end_header\n";
+    let (_, header) = ::header::header(input, &Default::default()).unwrap();
+    assert_eq!(vec![Diagnostic { line: 6, issue: Issue::DuplicateProperty("x".to_string()) }],
+               header.validate());
+}
+
+#[test]
+fn strict_validation_test() {
+    let input = b"ply\nformat ascii 1.0\nelement vertex 1\nelement face 1\nproperty float x\n\
+                  end_header\n";
+    assert!(::header::header(input, &Default::default()).is_ok());
+    let strict = ::ReadOptions { strict: true, ..Default::default() };
+    match ::header::header(input, &strict) {
+        Err(::PlyError::InvalidHeader(errors)) => {
+            let issue = Issue::NoProperties("vertex".to_string());
+            assert_eq!(vec![Diagnostic { line: 3, issue }], errors)
+        }
+        other => panic!("unexpected result {:?}", other.map(|(_, header)| header)),
+    }
+}
+
+#[test]
+fn duplicate_element_test() {
+    let input = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty int x\nelement vertex 1\n\
+                  property float y\nend_header\n1\n2.5\n";
+    match ::parse(input) {
+        Err(::PlyError::InvalidHeader(errors)) => {
+            let issue = Issue::DuplicateElement("vertex".to_string());
+            assert_eq!(vec![Diagnostic { line: 5, issue }], errors)
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
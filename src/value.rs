@@ -0,0 +1,155 @@
+use body::Value;
+use error::ConversionError;
+use header::ValueKind;
+use std::convert::TryFrom;
+
+impl Value {
+    /// The type of the value.
+    pub fn kind(&self) -> ValueKind {
+        match *self {
+            Value::Int8(_) => ValueKind::Int8,
+            Value::UInt8(_) => ValueKind::UInt8,
+            Value::Int16(_) => ValueKind::Int16,
+            Value::UInt16(_) => ValueKind::UInt16,
+            Value::Int32(_) => ValueKind::Int32,
+            Value::UInt32(_) => ValueKind::UInt32,
+            Value::Int64(_) => ValueKind::Int64,
+            Value::UInt64(_) => ValueKind::UInt64,
+            Value::Float32(_) => ValueKind::Float32,
+            Value::Float64(_) => ValueKind::Float64,
+        }
+    }
+
+    /// The value as an integer wide enough for every integer kind. Floats must be whole numbers,
+    /// those beyond the range of 'i128' saturate, which is out of range for every target anyway.
+    fn integer(&self) -> Result<i128, ConversionError> {
+        let float = match *self {
+            Value::Int8(v) => return Ok(v.into()),
+            Value::UInt8(v) => return Ok(v.into()),
+            Value::Int16(v) => return Ok(v.into()),
+            Value::UInt16(v) => return Ok(v.into()),
+            Value::Int32(v) => return Ok(v.into()),
+            Value::UInt32(v) => return Ok(v.into()),
+            Value::Int64(v) => return Ok(v.into()),
+            Value::UInt64(v) => return Ok(v.into()),
+            Value::Float32(v) => f64::from(v),
+            Value::Float64(v) => v,
+        };
+        if float.is_infinite() {
+            Err(ConversionError::Overflow)
+        } else if float.fract() != 0.0 || float.is_nan() {
+            Err(ConversionError::PrecisionLoss)
+        } else {
+            Ok(float as i128)
+        }
+    }
+
+    /// The value as an 'f64', failing for integers beyond 2^53 that an 'f64' cannot hold exactly.
+    pub fn as_f64(&self) -> Result<f64, ConversionError> {
+        f64::try_from(*self)
+    }
+
+    /// The value as an 'i64', failing if it is out of range or a float with a fractional part.
+    pub fn as_i64(&self) -> Result<i64, ConversionError> {
+        i64::try_from(*self)
+    }
+
+    /// The value as a 'u64', failing if it is out of range or a float with a fractional part.
+    pub fn as_u64(&self) -> Result<u64, ConversionError> {
+        u64::try_from(*self)
+    }
+}
+
+macro_rules! try_from_integer {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<Value> for $t {
+                type Error = ConversionError;
+
+                fn try_from(value: Value) -> Result<Self, ConversionError> {
+                    <$t>::try_from(value.integer()?).map_err(|_| ConversionError::Overflow)
+                }
+            }
+        )*
+    }
+}
+
+try_from_integer!(i8, u8, i16, u16, i32, u32, i64, u64);
+
+macro_rules! try_from_float {
+    ($($t:ident),*) => {
+        $(
+            impl TryFrom<Value> for $t {
+                type Error = ConversionError;
+
+                fn try_from(value: Value) -> Result<Self, ConversionError> {
+                    match value {
+                        Value::Float32(v) => {
+                            let narrow = v as $t;
+                            if narrow.is_infinite() && v.is_finite() {
+                                Err(ConversionError::Overflow)
+                            } else if f64::from(narrow) != f64::from(v) && !v.is_nan() {
+                                Err(ConversionError::PrecisionLoss)
+                            } else {
+                                Ok(narrow)
+                            }
+                        }
+                        Value::Float64(v) => {
+                            let narrow = v as $t;
+                            if narrow.is_infinite() && v.is_finite() {
+                                Err(ConversionError::Overflow)
+                            } else if f64::from(narrow) != v && !v.is_nan() {
+                                Err(ConversionError::PrecisionLoss)
+                            } else {
+                                Ok(narrow)
+                            }
+                        }
+                        _ => {
+                            let v = value.integer()?;
+                            let float = v as $t;
+                            if float as i128 == v {
+                                Ok(float)
+                            } else {
+                                Err(ConversionError::PrecisionLoss)
+                            }
+                        }
+                    }
+                }
+            }
+        )*
+    }
+}
+
+try_from_float!(f32, f64);
+
+#[test]
+fn kind_test() {
+    assert_eq!(ValueKind::UInt16, Value::UInt16(3).kind());
+    assert_eq!(ValueKind::Float64, Value::Float64(3.0).kind());
+}
+
+#[test]
+fn as_test() {
+    assert_eq!(Ok(255.0), Value::UInt8(255).as_f64());
+    assert_eq!(Ok(-3), Value::Float32(-3.0).as_i64());
+    assert_eq!(Ok(u64::MAX), Value::UInt64(u64::MAX).as_u64());
+    assert_eq!(Err(ConversionError::Overflow), Value::Int8(-1).as_u64());
+    assert_eq!(Err(ConversionError::Overflow), Value::UInt64(u64::MAX).as_i64());
+    assert_eq!(Err(ConversionError::PrecisionLoss), Value::Float64(0.5).as_i64());
+    assert_eq!(Err(ConversionError::PrecisionLoss), Value::Float64(f64::NAN).as_i64());
+    assert_eq!(Err(ConversionError::Overflow), Value::Float64(1e30).as_u64());
+    assert_eq!(Err(ConversionError::PrecisionLoss), Value::UInt64((1 << 53) + 1).as_f64());
+}
+
+#[test]
+fn try_from_test() {
+    assert_eq!(Ok(200u8), u8::try_from(Value::Int32(200)));
+    assert_eq!(Err(ConversionError::Overflow), i8::try_from(Value::Int32(200)));
+    assert_eq!(Err(ConversionError::Overflow), u16::try_from(Value::Float32(-1.0)));
+    assert_eq!(Ok(0.5f32), f32::try_from(Value::Float64(0.5)));
+    assert_eq!(Err(ConversionError::PrecisionLoss), f32::try_from(Value::Float64(0.1)));
+    assert_eq!(Err(ConversionError::Overflow), f32::try_from(Value::Float64(1e300)));
+    assert_eq!(Err(ConversionError::PrecisionLoss), f32::try_from(Value::Int32((1 << 24) + 1)));
+    assert!(f32::try_from(Value::Float64(f64::NAN)).unwrap().is_nan());
+    assert_eq!(Ok(f64::INFINITY), f64::try_from(Value::Float32(f32::INFINITY)));
+}
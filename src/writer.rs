@@ -0,0 +1,392 @@
+use body::{Ply, PropertyValue, Record, Value};
+use error::PlyError;
+use header::{is_name_char, layout, Comment, Element, FormatKind, Header, Line, PropertyKind,
+             ValueKind};
+use std::io::{BufWriter, Write};
+use std::iter::once;
+
+fn type_name(value_kind: ValueKind) -> &'static str {
+    match value_kind {
+        ValueKind::Int8 => "char",
+        ValueKind::UInt8 => "uchar",
+        ValueKind::Int16 => "short",
+        ValueKind::UInt16 => "ushort",
+        ValueKind::Int32 => "int",
+        ValueKind::UInt32 => "uint",
+        ValueKind::Int64 => "int64",
+        ValueKind::UInt64 => "uint64",
+        ValueKind::Float32 => "float",
+        ValueKind::Float64 => "double",
+    }
+}
+
+fn format_name(format_kind: &FormatKind) -> &'static str {
+    match *format_kind {
+        FormatKind::Ascii => "ascii",
+        FormatKind::BigEndian => "binary_big_endian",
+        FormatKind::LittleEndian => "binary_little_endian",
+    }
+}
+
+fn write_comment<W: Write>(writer: &mut W,
+                           keyword: &str,
+                           comment: &Comment)
+                           -> Result<(), PlyError> {
+    if comment.text.is_empty() {
+        writeln!(writer, "{}", keyword)?;
+    } else {
+        writeln!(writer, "{} {}", keyword, comment.text)?;
+    }
+    Ok(())
+}
+
+/// Makes sure that every part of 'header' is written in a way the parser reads back the same.
+fn check_header(header: &Header) -> Result<(), PlyError> {
+    let version = header.format.version;
+    if version.major < 0 || version.minor < 0 {
+        return Err(PlyError::BadVersion { version });
+    }
+    for comment in header.comments.iter().chain(&header.obj_infos) {
+        // The parser drops the whitespace after the keyword, and ends the text at a line break.
+        if comment.text.starts_with([' ', '\t']) || comment.text.contains(['\r', '\n']) {
+            return Err(PlyError::BadComment { text: comment.text.clone() });
+        }
+    }
+    for (index, element) in header.elements.iter().enumerate() {
+        if element.count < 0 {
+            return Err(PlyError::BadCount {
+                element: index,
+                count: element.count,
+            });
+        }
+    }
+    let names = header.elements.iter().flat_map(|element| {
+        once(&element.name).chain(element.properties.iter().map(|property| &property.name))
+    });
+    for name in names {
+        if name.is_empty() || !name.bytes().all(is_name_char) {
+            return Err(PlyError::BadName { name: name.clone() });
+        }
+    }
+    Ok(())
+}
+
+/// Writes 'header' including the final 'end_header' line. Comments and obj_infos are placed by
+/// their 'Comment::line', so a parsed header is written back with the same layout, except for
+/// blank lines and comments before the 'format' line, which are written right after it.
+/// Fails without writing anything if a name is empty or contains whitespace, a comment contains
+/// a line break or starts with whitespace, or a count or the version is negative.
+pub fn write_header<W: Write>(writer: &mut W, header: &Header) -> Result<(), PlyError> {
+    check_header(header)?;
+    for line in layout(header) {
+        match line {
+            Line::Magic => writeln!(writer, "ply")?,
+            Line::Format(format) => {
+                writeln!(writer,
+                         "format {} {}.{}",
+                         format_name(&format.kind),
+                         format.version.major,
+                         format.version.minor)?
+            }
+            Line::Comment(comment) => write_comment(writer, "comment", comment)?,
+            Line::ObjInfo(obj_info) => write_comment(writer, "obj_info", obj_info)?,
+            Line::Element(element) => {
+                writeln!(writer, "element {} {}", element.name, element.count)?
+            }
+            Line::Property(property) => {
+                match property.kind {
+                    PropertyKind::Scalar(value_kind) => {
+                        writeln!(writer, "property {} {}", type_name(value_kind), property.name)?
+                    }
+                    PropertyKind::List(count_kind, item_kind) => {
+                        writeln!(writer,
+                                 "property list {} {} {}",
+                                 type_name(count_kind),
+                                 type_name(item_kind),
+                                 property.name)?
+                    }
+                }
+            }
+            Line::EndHeader => writeln!(writer, "end_header")?,
+        }
+    }
+    Ok(())
+}
+
+/// Converts a list length into a value of the declared count type, if it fits.
+fn list_count(len: usize, count_kind: ValueKind) -> Option<Value> {
+    let len = len as u64;
+    match count_kind {
+        ValueKind::Int8 if len <= i8::MAX as u64 => Some(Value::Int8(len as i8)),
+        ValueKind::UInt8 if len <= u8::MAX as u64 => Some(Value::UInt8(len as u8)),
+        ValueKind::Int16 if len <= i16::MAX as u64 => Some(Value::Int16(len as i16)),
+        ValueKind::UInt16 if len <= u16::MAX as u64 => Some(Value::UInt16(len as u16)),
+        ValueKind::Int32 if len <= i32::MAX as u64 => Some(Value::Int32(len as i32)),
+        ValueKind::UInt32 if len <= u32::MAX as u64 => Some(Value::UInt32(len as u32)),
+        ValueKind::Int64 if len <= i64::MAX as u64 => Some(Value::Int64(len as i64)),
+        ValueKind::UInt64 => Some(Value::UInt64(len)),
+        _ => None,
+    }
+}
+
+fn write_ascii_value<W: Write>(writer: &mut W, value: &Value) -> Result<(), PlyError> {
+    match *value {
+        Value::Int8(v) => write!(writer, "{}", v)?,
+        Value::UInt8(v) => write!(writer, "{}", v)?,
+        Value::Int16(v) => write!(writer, "{}", v)?,
+        Value::UInt16(v) => write!(writer, "{}", v)?,
+        Value::Int32(v) => write!(writer, "{}", v)?,
+        Value::UInt32(v) => write!(writer, "{}", v)?,
+        Value::Int64(v) => write!(writer, "{}", v)?,
+        Value::UInt64(v) => write!(writer, "{}", v)?,
+        // 'Display' for floats prints the shortest representation that parses back exactly.
+        Value::Float32(v) => write!(writer, "{}", v)?,
+        Value::Float64(v) => write!(writer, "{}", v)?,
+    }
+    Ok(())
+}
+
+fn write_binary_value<W: Write>(writer: &mut W,
+                                value: &Value,
+                                big_endian: bool)
+                                -> Result<(), PlyError> {
+    macro_rules! bytes {
+        ($v:expr) => (if big_endian { $v.to_be_bytes() } else { $v.to_le_bytes() })
+    }
+    match *value {
+        Value::Int8(v) => writer.write_all(&bytes!(v))?,
+        Value::UInt8(v) => writer.write_all(&bytes!(v))?,
+        Value::Int16(v) => writer.write_all(&bytes!(v))?,
+        Value::UInt16(v) => writer.write_all(&bytes!(v))?,
+        Value::Int32(v) => writer.write_all(&bytes!(v))?,
+        Value::UInt32(v) => writer.write_all(&bytes!(v))?,
+        Value::Int64(v) => writer.write_all(&bytes!(v))?,
+        Value::UInt64(v) => writer.write_all(&bytes!(v))?,
+        Value::Float32(v) => writer.write_all(&bytes!(v))?,
+        Value::Float64(v) => writer.write_all(&bytes!(v))?,
+    }
+    Ok(())
+}
+
+fn write_value<W: Write>(writer: &mut W,
+                         format_kind: &FormatKind,
+                         value: &Value,
+                         first: bool)
+                         -> Result<(), PlyError> {
+    match *format_kind {
+        FormatKind::Ascii => {
+            if !first {
+                writer.write_all(b" ")?;
+            }
+            write_ascii_value(writer, value)
+        }
+        FormatKind::LittleEndian => write_binary_value(writer, value, false),
+        FormatKind::BigEndian => write_binary_value(writer, value, true),
+    }
+}
+
+/// Returns true if 'record' holds a value of the declared type for every property of 'element'.
+fn matches_element(element: &Element, record: &Record) -> bool {
+    record.len() == element.properties.len() &&
+    element.properties.iter().zip(record).all(|(property, value)| {
+        match (&property.kind, value) {
+            (PropertyKind::Scalar(kind), PropertyValue::Scalar(value)) => value.kind() == *kind,
+            (PropertyKind::List(count_kind, item_kind), PropertyValue::List(items)) => {
+                list_count(items.len(), *count_kind).is_some() &&
+                items.iter().all(|item| item.kind() == *item_kind)
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Writes a single record, which must have been checked with 'matches_element'.
+fn write_record<W: Write>(writer: &mut W,
+                          format_kind: &FormatKind,
+                          element: &Element,
+                          record: &Record)
+                          -> Result<(), PlyError> {
+    for (index, (property, value)) in element.properties.iter().zip(record).enumerate() {
+        let first = index == 0;
+        match (&property.kind, value) {
+            (PropertyKind::List(count_kind, _), PropertyValue::List(items)) => {
+                let count = list_count(items.len(), *count_kind)
+                    .expect("record was checked against the element");
+                write_value(writer, format_kind, &count, first)?;
+                for item in items {
+                    write_value(writer, format_kind, item, false)?;
+                }
+            }
+            (_, PropertyValue::Scalar(value)) => {
+                write_value(writer, format_kind, value, first)?
+            }
+            _ => unreachable!("record was checked against the element"),
+        }
+    }
+    if *format_kind == FormatKind::Ascii {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Writes 'ply' in the format given in its header. The records in 'payload' must match the
+/// element declarations exactly, otherwise 'PlyError::MismatchedRecord' is returned and the
+/// output is incomplete.
+pub fn write<W: Write>(writer: W, ply: &Ply) -> Result<(), PlyError> {
+    let mut writer = BufWriter::new(writer);
+    write_header(&mut writer, &ply.header)?;
+    for (index, element) in ply.header.elements.iter().enumerate() {
+        let records = ply.payload.get(&element.name).map_or(&[][..], |records| &records[..]);
+        for (record_index, record) in records.iter().enumerate() {
+            if record_index as i64 >= element.count || !matches_element(element, record) {
+                return Err(PlyError::MismatchedRecord {
+                    element: index,
+                    record: record_index,
+                });
+            }
+            write_record(&mut writer, &ply.header.format.kind, element, record)?;
+        }
+        if (records.len() as i64) < element.count {
+            return Err(PlyError::MismatchedRecord {
+                element: index,
+                record: records.len(),
+            });
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+fn beethoven() -> Ply {
+    ::read(::std::fs::File::open("testdata/beethoven.ply").unwrap()).unwrap()
+}
+
+#[test]
+fn write_header_round_trip_test() {
+    use header::header;
+    use options::ReadOptions;
+
+    let mut ply = beethoven();
+    ply.header.comments = vec![Comment {
+                                   line: 3,
+                                   text: "made by ply".into(),
+                               },
+                               Comment {
+                                   line: 5,
+                                   text: "".into(),
+                               }];
+    ply.header.obj_infos = vec![Comment {
+                                    line: 4,
+                                    text: "scanner 3".into(),
+                                }];
+    ply.header.elements[0].properties[0].kind = PropertyKind::Scalar(ValueKind::UInt64);
+    // Declarations as built by hand, without the lines they were parsed from.
+    let forget_lines = |header: &mut Header| {
+        for element in &mut header.elements {
+            element.line = 0;
+            element.properties.iter_mut().for_each(|property| property.line = 0);
+        }
+    };
+    forget_lines(&mut ply.header);
+    let mut written = Vec::new();
+    write_header(&mut written, &ply.header).unwrap();
+    let (rest, mut parsed) = header(&written, &ReadOptions::default()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(6, parsed.elements[0].line);
+    forget_lines(&mut parsed);
+    assert_eq!(ply.header, parsed);
+
+    let mut rewritten = Vec::new();
+    write_header(&mut rewritten, &parsed).unwrap();
+    assert_eq!(written, rewritten);
+}
+
+#[test]
+fn write_header_layout_test() {
+    use header::header;
+    use options::ReadOptions;
+
+    let input = b"ply\nformat ascii 1.0\ncomment after format\nelement vertex 3\n\
+                  comment between element and property\nproperty float x\nobj_info in between\n\
+                  property float y\nelement face 1\nproperty list uchar int vertex_index\n\
+                  comment last\nend_header\n";
+    let (_, parsed) = header(input, &ReadOptions::default()).unwrap();
+    let mut written = Vec::new();
+    write_header(&mut written, &parsed).unwrap();
+    assert_eq!(&input[..], &written[..]);
+
+    // Blank lines are dropped without moving comments, and nothing is written before 'format'.
+    let input = b"ply\ncomment first\nformat ascii 1.0\n\ncomment x\nelement v 1\n\n\
+                  obj_info y\nproperty int i\nend_header\n";
+    let (_, mut parsed) = header(input, &ReadOptions::default()).unwrap();
+    parsed.comments.push(Comment {
+        line: 0,
+        text: "added".into(),
+    });
+    let mut written = Vec::new();
+    write_header(&mut written, &parsed).unwrap();
+    assert_eq!(&b"ply\nformat ascii 1.0\ncomment added\ncomment first\ncomment x\nelement v 1\n\
+                  obj_info y\nproperty int i\nend_header\n"[..],
+               &written[..]);
+}
+
+#[test]
+fn write_round_trip_test() {
+    let mut ply = beethoven();
+    for &format_kind in &[FormatKind::Ascii, FormatKind::LittleEndian, FormatKind::BigEndian] {
+        ply.header.format.kind = format_kind;
+        let mut written = Vec::new();
+        write(&mut written, &ply).unwrap();
+        assert_eq!(ply, ::parse(&written).unwrap());
+    }
+}
+
+#[test]
+fn write_mismatched_record_test() {
+    let mut ply = beethoven();
+    ply.payload.get_mut("face").unwrap()[3] = vec![PropertyValue::List(vec![Value::Int8(1)])];
+    assert!(matches!(write(Vec::new(), &ply),
+                     Err(PlyError::MismatchedRecord { element: 1, record: 3 })));
+
+    let mut ply = beethoven();
+    ply.payload.get_mut("vertex").unwrap().pop();
+    assert!(matches!(write(Vec::new(), &ply),
+                     Err(PlyError::MismatchedRecord { element: 0, record: 2520 })));
+}
+
+#[test]
+fn write_bad_header_test() {
+    let mut ply = beethoven();
+    ply.header.comments.push(Comment {
+        line: 3,
+        text: "a\nelement bogus 5".into(),
+    });
+    let mut written = Vec::new();
+    assert!(matches!(write(&mut written, &ply), Err(PlyError::BadComment { .. })));
+    assert!(written.is_empty());
+
+    let mut ply = beethoven();
+    ply.header.obj_infos.push(Comment {
+        line: 0,
+        text: "  leading".into(),
+    });
+    assert!(matches!(write(&mut Vec::new(), &ply), Err(PlyError::BadComment { .. })));
+
+    let mut ply = beethoven();
+    ply.header.elements[1].count = -1;
+    assert!(matches!(write_header(&mut Vec::new(), &ply.header),
+                     Err(PlyError::BadCount { element: 1, count: -1 })));
+
+    let mut ply = beethoven();
+    ply.header.format.version.minor = -1;
+    assert!(matches!(write_header(&mut Vec::new(), &ply.header),
+                     Err(PlyError::BadVersion { .. })));
+
+    for name in &["", "vertex index"] {
+        let mut ply = beethoven();
+        ply.header.elements[0].properties[1].name = name.to_string();
+        assert!(matches!(write_header(&mut Vec::new(), &ply.header),
+                         Err(PlyError::BadName { .. })));
+    }
+}